@@ -0,0 +1,165 @@
+use termcolor::{Color, ColorSpec, WriteColor};
+
+/// The documentation of an error code, declared with [`error_codes!`](crate::error_codes).
+#[derive(Debug)]
+pub struct ErrorCode {
+    pub code: u16,
+    pub title: &'static str,
+    /// A long explanation in Markdown.
+    pub explanation: &'static str,
+    pub examples: &'static [&'static str],
+}
+
+#[derive(Debug)]
+pub struct Registry {
+    codes: &'static [ErrorCode],
+}
+
+/// Declares a static [`Registry`] of error codes.
+///
+/// ```ignore
+/// error_codes! {
+///     pub static ERROR_CODES {
+///         42 => {
+///             title: "mismatched types",
+///             explanation: "The expression does not have the type that was expected here.",
+///             examples: ["let x: i32 = \"hello\";"],
+///         },
+///     }
+/// }
+/// ```
+pub macro error_codes {
+    ($vis:vis static $name:ident {
+        $($code:literal => {
+            title: $title:expr,
+            explanation: $expl:expr
+            $(, examples: [$($example:expr),* $(,)?])?
+            $(,)?
+        }),* $(,)?
+    }) => {
+        $vis static $name: $crate::Registry = $crate::Registry::new(&[
+            $($crate::ErrorCode {
+                code: $code,
+                title: $title,
+                explanation: $expl,
+                examples: &[$($($example),*)?],
+            }),*
+        ]);
+    }
+}
+
+impl Registry {
+    pub const fn new(codes: &'static [ErrorCode]) -> Registry {
+        Registry { codes }
+    }
+
+    pub fn codes(&self) -> &'static [ErrorCode] {
+        self.codes
+    }
+
+    pub fn find(&self, code: u16) -> Option<&'static ErrorCode> {
+        self.codes.iter().find(|c| c.code == code)
+    }
+
+    /// Looks up a code as written by a user, e.g. `42`, `0042` or `E0042`.
+    pub fn lookup(&self, query: &str) -> Option<&'static ErrorCode> {
+        let digits = query
+            .trim()
+            .trim_start_matches(|c: char| c.is_ascii_alphabetic());
+
+        digits.parse().ok().and_then(|code| self.find(code))
+    }
+}
+
+impl ErrorCode {
+    /// Renders the explanation for display in a terminal.
+    pub fn write(&self, mut writer: impl WriteColor) -> std::io::Result<()> {
+        let mut bold = ColorSpec::new();
+        bold.set_bold(true);
+
+        writer.set_color(&bold)?;
+        writeln!(writer, "[{:0>4}]: {}", self.code, self.title)?;
+        writer.reset()?;
+        writeln!(writer)?;
+        write_markdown(&mut writer, self.explanation)?;
+
+        if !self.examples.is_empty() {
+            writeln!(writer)?;
+            writer.set_color(&bold)?;
+            writeln!(writer, "Examples")?;
+            writer.reset()?;
+
+            for example in self.examples {
+                writeln!(writer)?;
+                write_code(&mut writer, example)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A minimal Markdown renderer: headings are bold, code is highlighted and everything else
+/// is written as-is.
+fn write_markdown(mut writer: impl WriteColor, text: &str) -> std::io::Result<()> {
+    let mut bold = ColorSpec::new();
+    bold.set_bold(true);
+    let mut in_code = false;
+    let mut code = String::new();
+
+    for line in text.trim().lines() {
+        if line.trim_start().starts_with("```") {
+            if in_code {
+                write_code(&mut writer, &code)?;
+                code.clear();
+            }
+
+            in_code = !in_code;
+        } else if in_code {
+            code.push_str(line);
+            code.push('\n');
+        } else if line.starts_with('#') {
+            writer.set_color(&bold)?;
+            writeln!(writer, "{}", line.trim_start_matches('#').trim())?;
+            writer.reset()?;
+        } else {
+            write_inline(&mut writer, line)?;
+        }
+    }
+
+    if in_code {
+        write_code(&mut writer, &code)?;
+    }
+
+    Ok(())
+}
+
+fn write_inline(mut writer: impl WriteColor, line: &str) -> std::io::Result<()> {
+    let mut code = ColorSpec::new();
+    code.set_fg(Some(Color::Cyan));
+
+    for (i, part) in line.split('`').enumerate() {
+        if i % 2 == 1 {
+            writer.set_color(&code)?;
+            write!(writer, "{}", part)?;
+            writer.reset()?;
+        } else {
+            write!(writer, "{}", part)?;
+        }
+    }
+
+    writeln!(writer)
+}
+
+fn write_code(mut writer: impl WriteColor, code: &str) -> std::io::Result<()> {
+    let mut color = ColorSpec::new();
+    color.set_fg(Some(Color::Cyan));
+
+    writer.set_color(&color)?;
+
+    for line in code.trim_end().lines() {
+        writeln!(writer, "    {}", line)?;
+    }
+
+    writer.reset()
+}
@@ -1,21 +1,64 @@
-use crate::Span;
+use crate::{Lint, Span};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Diagnostic {
     pub severity: Severity,
     pub code: Option<u16>,
     pub message: String,
     pub labels: Vec<Label>,
+    pub suggestions: Vec<Suggestion>,
+    /// The lint this diagnostic belongs to, which decides its final severity.
+    pub lint: Option<&'static Lint>,
+    /// Notes and help with their own labels, rendered as sections below this diagnostic.
+    pub children: Vec<Diagnostic>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Label {
     pub span: Option<Span>,
     pub message: Option<String>,
     pub severity: Severity,
+    pub style: LabelStyle,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// Whether a label marks the main location of a diagnostic or only provides context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelStyle {
+    /// Drawn with `^` in the color of the diagnostic.
+    Primary,
+    /// Drawn with `-` in a muted color.
+    Secondary,
+}
+
+/// A proposed edit, made up of one or more replacements that are applied together.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Suggestion {
+    pub message: String,
+    pub substitutions: Vec<Substitution>,
+    pub applicability: Applicability,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Substitution {
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// How confident the author of a suggestion is that it can be applied blindly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Applicability {
+    /// The suggestion is definitely what the user intended and can be applied automatically.
+    MachineApplicable,
+    /// The suggestion may be what the user intended, but it is uncertain.
+    MaybeIncorrect,
+    /// The suggestion contains placeholders like `(...)` that the user must fill in.
+    HasPlaceholders,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     Bug,
     Error,
@@ -35,23 +78,117 @@ impl Diagnostic {
             code: code.into(),
             message: message.into(),
             labels: Vec::new(),
+            suggestions: Vec::new(),
+            lint: None,
+            children: Vec::new(),
         }
     }
 
-    pub fn label<M, S>(mut self, severity: Severity, span: S, message: M) -> Diagnostic
+    /// The span of the first primary label, or of the first label that has one if none is
+    /// primary.
+    pub fn primary_span(&self) -> Option<Span> {
+        self.find_primary()
+            .or_else(|| self.labels.iter().find(|l| l.span.is_some()))
+            .and_then(|l| l.span)
+    }
+
+    fn find_primary(&self) -> Option<&Label> {
+        self.labels
+            .iter()
+            .find(|l| l.span.is_some() && l.style == LabelStyle::Primary)
+    }
+
+    /// Adds a label, which is primary if it is the first label with a span.
+    pub fn label<M, S>(self, severity: Severity, span: S, message: M) -> Diagnostic
     where
         M: IntoOption<String>,
         S: Into<Option<Span>>,
+    {
+        let span = span.into();
+        let style = if span.is_some() && self.find_primary().is_none() {
+            LabelStyle::Primary
+        } else {
+            LabelStyle::Secondary
+        };
+
+        self.styled_label(style, severity, span, message)
+    }
+
+    pub fn primary_label<M>(self, span: impl Into<Span>, message: M) -> Diagnostic
+    where
+        M: IntoOption<String>,
+    {
+        let severity = self.severity;
+
+        self.styled_label(LabelStyle::Primary, severity, Some(span.into()), message)
+    }
+
+    pub fn secondary_label<M>(self, span: impl Into<Span>, message: M) -> Diagnostic
+    where
+        M: IntoOption<String>,
+    {
+        let severity = self.severity;
+
+        self.styled_label(LabelStyle::Secondary, severity, Some(span.into()), message)
+    }
+
+    fn styled_label<M>(
+        mut self,
+        style: LabelStyle,
+        severity: Severity,
+        span: Option<Span>,
+        message: M,
+    ) -> Diagnostic
+    where
+        M: IntoOption<String>,
     {
         self.labels.push(Label {
             severity,
-            span: span.into(),
+            span,
             message: message.into_option(),
+            style,
         });
 
         self
     }
 
+    pub fn suggestion(
+        self,
+        span: impl Into<Span>,
+        replacement: impl Into<String>,
+        message: impl Into<String>,
+        applicability: Applicability,
+    ) -> Diagnostic {
+        self.multipart_suggestion(
+            vec![(span.into(), replacement.into())],
+            message,
+            applicability,
+        )
+    }
+
+    pub fn multipart_suggestion(
+        mut self,
+        parts: Vec<(Span, String)>,
+        message: impl Into<String>,
+        applicability: Applicability,
+    ) -> Diagnostic {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            substitutions: parts
+                .into_iter()
+                .map(|(span, replacement)| Substitution { span, replacement })
+                .collect(),
+            applicability,
+        });
+
+        self
+    }
+
+    pub fn lint(mut self, lint: &'static Lint) -> Diagnostic {
+        self.lint = Some(lint);
+        self
+    }
+
     pub fn note(self, message: impl Into<String>) -> Diagnostic {
         self.label(Severity::Info, None, Some(message.into()))
     }
@@ -59,6 +196,28 @@ impl Diagnostic {
     pub fn help(self, message: impl Into<String>) -> Diagnostic {
         self.label(Severity::Help, None, Some(message.into()))
     }
+
+    pub fn child(mut self, child: Diagnostic) -> Diagnostic {
+        self.children.push(child);
+        self
+    }
+
+    /// Adds a note with its own code excerpt, e.g. pointing at a previous definition.
+    pub fn span_note(self, span: impl Into<Span>, message: impl Into<String>) -> Diagnostic {
+        self.child(Diagnostic::new(Severity::Info, None, message).label(
+            Severity::Info,
+            span.into(),
+            None::<String>,
+        ))
+    }
+
+    pub fn span_help(self, span: impl Into<Span>, message: impl Into<String>) -> Diagnostic {
+        self.child(Diagnostic::new(Severity::Help, None, message).label(
+            Severity::Help,
+            span.into(),
+            None::<String>,
+        ))
+    }
 }
 
 impl Severity {
@@ -86,6 +245,9 @@ impl Severity {
         }
     }
 
+    #[deprecated(
+        note = "label markers are configured with `RenderOptions::primary_marker` and `RenderOptions::secondary_marker`"
+    )]
     pub fn symbol(&self) -> &'static str {
         match self {
             Severity::Bug => "^",
@@ -1,4 +1,6 @@
 pub mod build;
+pub mod json;
+pub mod sarif;
 pub mod snippet;
 pub mod write;
 
@@ -9,11 +11,224 @@ pub mod write;
 // };
 // use std::collections::HashMap;
 
-pub fn emit(diagnostic: &crate::Diagnostic) {
-    let mut writer = termcolor::StandardStream::stderr(termcolor::ColorChoice::Auto);
-    let snippet = build::build(diagnostic);
+use crate::{Diagnostic, Registry};
+use std::io::Write;
+use termcolor::{ColorChoice, StandardStream, WriteColor};
 
-    snippet.write(&mut writer).unwrap();
+pub trait Emitter {
+    fn emit(&mut self, diagnostic: &Diagnostic) -> std::io::Result<()>;
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Renders diagnostics as annotated source snippets.
+pub struct HumanEmitter<W> {
+    writer: W,
+    registry: Option<&'static Registry>,
+    options: RenderOptions,
+}
+
+/// How [`HumanEmitter`] lays out source snippets.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// The number of columns available. Source lines that are longer are cut off with `...`
+    /// around their annotations. `None` never cuts off lines.
+    pub width: Option<usize>,
+    /// The number of spaces a tab is expanded to.
+    pub tab_width: usize,
+    /// Draws multiline annotations with ASCII characters instead of Unicode box drawing
+    /// characters, for consoles and log files that cannot display them.
+    pub ascii: bool,
+    /// The number of unannotated lines shown before and after each annotated line.
+    pub context_lines: usize,
+    /// The number of lines shown at the start of a multiline annotation before the rest of
+    /// it is elided, up to its last two lines.
+    pub multiline_lines: usize,
+    /// The character that underlines primary labels.
+    pub primary_marker: char,
+    /// The character that underlines secondary labels.
+    pub secondary_marker: char,
+    /// Adds a note for every macro expansion a span went through, instead of only pointing
+    /// at the outermost macro invocation.
+    pub macro_backtrace: bool,
+}
+
+/// Renders each diagnostic as a single `file:line:col: severity[code]: message` line.
+pub struct ShortEmitter<W> {
+    writer: W,
+    labels: bool,
+}
+
+/// Writes each diagnostic as one JSON object per line.
+pub struct JsonEmitter<W> {
+    writer: W,
+}
+
+impl<E: Emitter + ?Sized> Emitter for Box<E> {
+    fn emit(&mut self, diagnostic: &Diagnostic) -> std::io::Result<()> {
+        (**self).emit(diagnostic)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        (**self).flush()
+    }
+}
+
+impl HumanEmitter<StandardStream> {
+    /// Writes to stderr, fitting the output to the width of the terminal.
+    pub fn stderr(color: ColorChoice) -> Self {
+        HumanEmitter::new(StandardStream::stderr(color)).with_options(RenderOptions {
+            width: RenderOptions::terminal_width(),
+            ..RenderOptions::default()
+        })
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            width: None,
+            tab_width: 4,
+            ascii: false,
+            context_lines: 0,
+            multiline_lines: 4,
+            primary_marker: '^',
+            secondary_marker: '-',
+            macro_backtrace: false,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// The character drawn in place of the box drawing character `c`.
+    pub(crate) fn glyph(&self, c: char) -> char {
+        if !self.ascii {
+            return c;
+        }
+
+        match c {
+            '╭' => '/',
+            '│' | '└' => '|',
+            '┌' | '─' => '_',
+            c => c,
+        }
+    }
+
+    /// The width of the terminal on stderr, falling back to the `COLUMNS` environment
+    /// variable if stderr is not a terminal.
+    pub fn terminal_width() -> Option<usize> {
+        match terminal_size::terminal_size_of(std::io::stderr()) {
+            Some((terminal_size::Width(width), _)) => Some(width as usize),
+            None => std::env::var("COLUMNS").ok()?.parse().ok(),
+        }
+    }
+}
+
+impl<W: WriteColor> HumanEmitter<W> {
+    pub fn new(writer: W) -> Self {
+        HumanEmitter {
+            writer,
+            registry: None,
+            options: RenderOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Mentions the explanation of every code found in `registry`.
+    pub fn with_registry(mut self, registry: &'static Registry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: WriteColor> Emitter for HumanEmitter<W> {
+    fn emit(&mut self, diagnostic: &Diagnostic) -> std::io::Result<()> {
+        let diagnostic = build::with_expansion_notes(diagnostic, &self.options);
+
+        build::build(&diagnostic, &self.options).write(&mut self.writer, &self.options)?;
+
+        match (self.registry, diagnostic.code) {
+            (Some(registry), Some(code)) if registry.find(code).is_some() => {
+                write::write_explain_hint(&mut self.writer, code)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl ShortEmitter<StandardStream> {
+    pub fn stderr(color: ColorChoice) -> Self {
+        ShortEmitter::new(StandardStream::stderr(color))
+    }
+}
+
+impl<W: WriteColor> ShortEmitter<W> {
+    pub fn new(writer: W) -> Self {
+        ShortEmitter {
+            writer,
+            labels: false,
+        }
+    }
+
+    /// Also writes a line for every label besides the first, for notes and secondary spans.
+    pub fn with_labels(mut self, labels: bool) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: WriteColor> Emitter for ShortEmitter<W> {
+    fn emit(&mut self, diagnostic: &Diagnostic) -> std::io::Result<()> {
+        write::write_short(diagnostic, &mut self.writer, self.labels)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl JsonEmitter<std::io::Stderr> {
+    pub fn stderr() -> Self {
+        JsonEmitter::new(std::io::stderr())
+    }
+}
+
+impl<W: Write> JsonEmitter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonEmitter { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Emitter for JsonEmitter<W> {
+    fn emit(&mut self, diagnostic: &Diagnostic) -> std::io::Result<()> {
+        json::emit(diagnostic, &mut self.writer)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
 }
 
 /*pub fn emit(diagnostic: &crate::Diagnostic) {
@@ -1,12 +1,27 @@
 use super::snippet::*;
-use crate::{Diagnostic, FileId};
+use super::RenderOptions;
+use crate::{Diagnostic, FileId, LabelStyle, Severity, Span};
+use std::borrow::Cow;
 
-pub fn build(diag: &Diagnostic) -> Snippet {
+pub fn build<'a>(diag: &'a Diagnostic, options: &RenderOptions) -> Snippet<'a> {
     let mut snippet = Snippet {
         severity: diag.severity,
         message: &diag.message,
         code: diag.code,
+        primary: diag.primary_span(),
         parts: Vec::new(),
+        suggestions: &diag.suggestions,
+        notes: diag
+            .labels
+            .iter()
+            .filter(|l| l.span.is_none())
+            .filter_map(|l| Some((l.severity, l.message.as_deref()?)))
+            .collect(),
+        children: diag
+            .children
+            .iter()
+            .map(|child| build(child, options))
+            .collect(),
     };
 
     fn add_annotation_to_file<'a>(
@@ -16,7 +31,7 @@ pub fn build(diag: &Diagnostic) -> Snippet {
         ann: Annotation<'a>,
     ) {
         for part in files.iter_mut() {
-            if part.file.name == file.name {
+            if part.file == file {
                 for line in &mut part.lines {
                     if line.idx == idx {
                         line.annotations.push(ann);
@@ -46,11 +61,15 @@ pub fn build(diag: &Diagnostic) -> Snippet {
     let mut multiline = Vec::new();
 
     for lbl in &diag.labels {
-        if let None = lbl.span {
+        if lbl.span.is_none() {
             continue;
         }
 
         let span = lbl.span.unwrap();
+        let severity = match lbl.style {
+            LabelStyle::Primary => diag.severity,
+            LabelStyle::Secondary => lbl.severity,
+        };
         let lo = span.start;
         let mut hi = span.end;
 
@@ -62,7 +81,8 @@ pub fn build(diag: &Diagnostic) -> Snippet {
             multiline.push((
                 span.file,
                 MultilineAnnotation {
-                    severity: lbl.severity,
+                    severity,
+                    style: lbl.style,
                     depth: 1,
                     span,
                     line_start: lo.line,
@@ -79,7 +99,8 @@ pub fn build(diag: &Diagnostic) -> Snippet {
                 span.file,
                 lo.line,
                 Annotation {
-                    severity: lbl.severity,
+                    severity,
+                    style: lbl.style,
                     span,
                     start: lo.col,
                     end: hi.col,
@@ -90,7 +111,7 @@ pub fn build(diag: &Diagnostic) -> Snippet {
         }
     }
 
-    multiline.sort_by_key(|&(_, ref ml)| (ml.line_start, ml.line_end));
+    multiline.sort_by_key(|(_, ml)| (ml.line_start, ml.line_end));
 
     for (_, ann) in multiline.clone() {
         for (_, a) in multiline.iter_mut() {
@@ -106,13 +127,18 @@ pub fn build(diag: &Diagnostic) -> Snippet {
         }
     }
 
+    let ranges = multiline
+        .iter()
+        .map(|(file, ann)| (*file, ann.line_start, ann.line_end))
+        .collect::<Vec<_>>();
+
     for (file, ann) in multiline {
         let mut end_ann = ann.as_end();
 
         if !ann.overlaps_exactly {
             add_annotation_to_file(&mut snippet.parts, file, ann.line_start, ann.as_start());
 
-            let middle = std::cmp::min(ann.line_start + 4, ann.line_end);
+            let middle = std::cmp::min(ann.line_start + options.multiline_lines, ann.line_end);
 
             for line in ann.line_start + 1..middle {
                 add_annotation_to_file(&mut snippet.parts, file, line, ann.as_line());
@@ -130,10 +156,104 @@ pub fn build(diag: &Diagnostic) -> Snippet {
         add_annotation_to_file(&mut snippet.parts, file, ann.line_end, end_ann);
     }
 
+    if options.context_lines > 0 {
+        for part in &mut snippet.parts {
+            add_context(part, options.context_lines, &ranges);
+        }
+    }
+
+    if let Some(primary) = snippet.primary {
+        snippet.parts.sort_by_key(|p| p.file != primary.file);
+    }
+
     snippet.finalize();
     snippet
 }
 
+/// Adds a note pointing at the macro invocation that every label in a macro expansion came
+/// from, or with `macro_backtrace` one note for each expansion in between.
+pub fn with_expansion_notes<'a>(
+    diag: &'a Diagnostic,
+    options: &RenderOptions,
+) -> Cow<'a, Diagnostic> {
+    let spans = diag
+        .labels
+        .iter()
+        .filter_map(|l| l.span)
+        .filter(|s| !s.expn.is_root())
+        .collect::<Vec<_>>();
+
+    if spans.is_empty() {
+        return Cow::Borrowed(diag);
+    }
+
+    let mut diag = diag.clone();
+    let mut seen = Vec::<Span>::new();
+
+    for span in spans {
+        let backtrace = span.macro_backtrace();
+        let expansions = if options.macro_backtrace {
+            &backtrace[..]
+        } else {
+            &backtrace[backtrace.len().saturating_sub(1)..]
+        };
+
+        for expn in expansions {
+            if seen.contains(&expn.call_site) {
+                continue;
+            }
+
+            seen.push(expn.call_site);
+
+            let message = if options.macro_backtrace {
+                format!("in this expansion of {}", expn.kind.descr())
+            } else {
+                format!(
+                    "this {} originates in the {}",
+                    diag.severity.to_string(),
+                    expn.kind.descr()
+                )
+            };
+
+            let mut note = Diagnostic::new(Severity::Info, None, message)
+                .primary_label(expn.call_site, "in this macro invocation");
+
+            if let (true, Some(def_site)) = (options.macro_backtrace, expn.def_site) {
+                note = note.secondary_label(def_site, "defined here");
+            }
+
+            diag.children.push(note);
+        }
+    }
+
+    Cow::Owned(diag)
+}
+
+/// Adds up to `context` unannotated lines around every line of `part`, except inside the
+/// elided middle of a multiline annotation.
+fn add_context(part: &mut SnippetPart, context: usize, multiline: &[(FileId, usize, usize)]) {
+    let info = part.file.info();
+    let last = (info.line_count() - 1).saturating_sub(info.source.ends_with('\n') as usize);
+    let shown = part.lines.iter().map(|l| l.idx).collect::<Vec<_>>();
+
+    for idx in shown {
+        for ctx in idx.saturating_sub(context)..=(idx + context).min(last) {
+            let hidden = multiline
+                .iter()
+                .any(|&(file, start, end)| file == part.file && start < ctx && ctx < end);
+
+            if !hidden && !part.lines.iter().any(|l| l.idx == ctx) {
+                part.lines.push(Line {
+                    idx: ctx,
+                    annotations: Vec::new(),
+                });
+            }
+        }
+    }
+
+    part.lines.sort();
+}
+
 fn num_overlap(
     a_start: usize,
     a_end: usize,
@@ -0,0 +1,134 @@
+use crate::{
+    Applicability, Diagnostic, Label, LabelStyle, Severity, Span, Substitution, Suggestion,
+};
+use serde::Serialize;
+use std::io::Write;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonDiagnostic {
+    pub severity: Severity,
+    pub code: Option<u16>,
+    pub message: String,
+    pub labels: Vec<JsonLabel>,
+    pub suggestions: Vec<JsonSuggestion>,
+    pub children: Vec<JsonDiagnostic>,
+    /// The human-readable rendering, which includes the children and is therefore only set
+    /// on the top-level diagnostic.
+    pub rendered: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonLabel {
+    pub severity: Severity,
+    pub is_primary: bool,
+    pub message: Option<String>,
+    pub span: Option<JsonSpan>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonSuggestion {
+    pub message: String,
+    pub applicability: Applicability,
+    pub substitutions: Vec<JsonSubstitution>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonSubstitution {
+    pub span: JsonSpan,
+    pub replacement: String,
+}
+
+/// A span with 1-based lines and columns, as shown by the human renderer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonSpan {
+    pub file_name: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub column_start: usize,
+    pub column_end: usize,
+}
+
+pub fn emit(diagnostic: &Diagnostic, mut writer: impl Write) -> std::io::Result<()> {
+    let json = JsonDiagnostic::from(diagnostic);
+
+    serde_json::to_writer(&mut writer, &json)?;
+    writeln!(writer)
+}
+
+impl From<&Diagnostic> for JsonDiagnostic {
+    fn from(diag: &Diagnostic) -> JsonDiagnostic {
+        let mut rendered = termcolor::NoColor::new(Vec::new());
+        let options = super::RenderOptions::default();
+        let expanded = super::build::with_expansion_notes(diag, &options);
+        let _ = super::build::build(&expanded, &options).write(&mut rendered, &options);
+
+        JsonDiagnostic {
+            rendered: Some(String::from_utf8_lossy(&rendered.into_inner()).into_owned()),
+            ..JsonDiagnostic::child(diag)
+        }
+    }
+}
+
+impl JsonDiagnostic {
+    fn child(diag: &Diagnostic) -> JsonDiagnostic {
+        JsonDiagnostic {
+            severity: diag.severity,
+            code: diag.code,
+            message: diag.message.clone(),
+            labels: diag.labels.iter().map(JsonLabel::from).collect(),
+            suggestions: diag.suggestions.iter().map(JsonSuggestion::from).collect(),
+            children: diag.children.iter().map(JsonDiagnostic::child).collect(),
+            rendered: None,
+        }
+    }
+}
+
+impl From<&Label> for JsonLabel {
+    fn from(label: &Label) -> JsonLabel {
+        JsonLabel {
+            severity: label.severity,
+            is_primary: label.style == LabelStyle::Primary,
+            message: label.message.clone(),
+            span: label.span.map(JsonSpan::from),
+        }
+    }
+}
+
+impl From<&Suggestion> for JsonSuggestion {
+    fn from(suggestion: &Suggestion) -> JsonSuggestion {
+        JsonSuggestion {
+            message: suggestion.message.clone(),
+            applicability: suggestion.applicability,
+            substitutions: suggestion
+                .substitutions
+                .iter()
+                .map(JsonSubstitution::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<&Substitution> for JsonSubstitution {
+    fn from(sub: &Substitution) -> JsonSubstitution {
+        JsonSubstitution {
+            span: sub.span.into(),
+            replacement: sub.replacement.clone(),
+        }
+    }
+}
+
+impl From<Span> for JsonSpan {
+    fn from(span: Span) -> JsonSpan {
+        JsonSpan {
+            file_name: span.file.info().name.display().to_string(),
+            byte_start: span.start.offset,
+            byte_end: span.end.offset,
+            line_start: span.start.line + 1,
+            line_end: span.end.line + 1,
+            column_start: span.start.col + 1,
+            column_end: span.end.col + 1,
+        }
+    }
+}
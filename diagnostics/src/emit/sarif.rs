@@ -0,0 +1,334 @@
+use crate::{Diagnostic, FileId, Severity, Span};
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const ROOT_ID: &str = "SRCROOT";
+
+pub struct SarifOptions {
+    pub tool_name: String,
+    pub tool_version: Option<String>,
+    pub information_uri: Option<String>,
+    /// Artifact paths below this directory are written relative to it.
+    pub root: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Log {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Run {
+    tool: Tool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_uri_base_ids: Option<serde_json::Value>,
+    column_kind: &'static str,
+    artifacts: Vec<Artifact>,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    information_uri: Option<String>,
+    rules: Vec<Rule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Artifact {
+    location: ArtifactLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule_id: Option<String>,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related_locations: Vec<Location>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    physical_location: PhysicalLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<Message>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactLocation {
+    uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri_base_id: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+    byte_offset: usize,
+    byte_length: usize,
+}
+
+impl SarifOptions {
+    pub fn new(tool_name: impl Into<String>) -> Self {
+        SarifOptions {
+            tool_name: tool_name.into(),
+            tool_version: None,
+            information_uri: None,
+            root: None,
+        }
+    }
+}
+
+pub fn write(
+    diagnostics: &[Diagnostic],
+    options: &SarifOptions,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, &Log::new(diagnostics, options))?;
+    writeln!(writer)
+}
+
+impl Log {
+    pub fn new(diagnostics: &[Diagnostic], options: &SarifOptions) -> Log {
+        let mut files = Vec::<FileId>::new();
+        let mut rules = Vec::<u16>::new();
+        let mut results = Vec::new();
+
+        for diag in diagnostics {
+            if let Some(code) = diag.code {
+                if !rules.contains(&code) {
+                    rules.push(code);
+                }
+            }
+
+            results.push(SarifResult::new(diag, options, &mut files));
+        }
+
+        rules.sort_unstable();
+
+        Log {
+            schema: SCHEMA,
+            version: "2.1.0",
+            runs: vec![Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: options.tool_name.clone(),
+                        version: options.tool_version.clone(),
+                        information_uri: options.information_uri.clone(),
+                        rules: rules
+                            .into_iter()
+                            .map(|code| Rule { id: rule_id(code) })
+                            .collect(),
+                    },
+                },
+                original_uri_base_ids: options.root.as_ref().map(|root| {
+                    let mut uri = format!("file://{}", crate::file::path_to_uri(root));
+
+                    if !uri.ends_with('/') {
+                        uri.push('/');
+                    }
+
+                    serde_json::json!({ ROOT_ID: { "uri": uri } })
+                }),
+                column_kind: "unicodeCodePoints",
+                artifacts: files
+                    .iter()
+                    .map(|&file| Artifact {
+                        location: ArtifactLocation::new(file, options, None),
+                    })
+                    .collect(),
+                results,
+            }],
+        }
+    }
+}
+
+impl SarifResult {
+    fn new(diag: &Diagnostic, options: &SarifOptions, files: &mut Vec<FileId>) -> SarifResult {
+        let mut text = diag.message.clone();
+        let mut locations = Vec::new();
+        let mut related_locations = Vec::new();
+        let primary = diag.primary_span();
+
+        for label in &diag.labels {
+            match label.span {
+                Some(span) if locations.is_empty() && Some(span) == primary => {
+                    locations.push(Location::new(span, None, None, options, files));
+                }
+                Some(span) => {
+                    let message = label.message.clone().map(|text| Message { text });
+                    let id = Some(related_locations.len() + 1);
+
+                    related_locations.push(Location::new(span, id, message, options, files));
+                }
+                None => {
+                    if let Some(msg) = &label.message {
+                        text.push_str(&format!("\n{}: {}", label.severity.to_string(), msg));
+                    }
+                }
+            }
+        }
+
+        for child in &diag.children {
+            match child.primary_span() {
+                Some(span) => {
+                    let message = Some(Message {
+                        text: child.message.clone(),
+                    });
+                    let id = Some(related_locations.len() + 1);
+
+                    related_locations.push(Location::new(span, id, message, options, files));
+                }
+                None => text.push_str(&format!(
+                    "\n{}: {}",
+                    child.severity.to_string(),
+                    child.message
+                )),
+            }
+        }
+
+        SarifResult {
+            rule_id: diag.code.map(rule_id),
+            level: level(diag.severity),
+            message: Message { text },
+            locations,
+            related_locations,
+        }
+    }
+}
+
+impl Location {
+    fn new(
+        span: Span,
+        id: Option<usize>,
+        message: Option<Message>,
+        options: &SarifOptions,
+        files: &mut Vec<FileId>,
+    ) -> Location {
+        let index = match files.iter().position(|&f| f == span.file) {
+            Some(idx) => idx,
+            None => {
+                files.push(span.file);
+                files.len() - 1
+            }
+        };
+
+        Location {
+            id,
+            physical_location: PhysicalLocation {
+                artifact_location: ArtifactLocation::new(span.file, options, Some(index)),
+                region: Region::from(span),
+            },
+            message,
+        }
+    }
+}
+
+impl ArtifactLocation {
+    fn new(file: FileId, options: &SarifOptions, index: Option<usize>) -> ArtifactLocation {
+        let file = file.info();
+        let name = &file.name;
+        let relative = options
+            .root
+            .as_deref()
+            .and_then(|root| name.strip_prefix(root).ok());
+
+        match relative {
+            Some(path) => ArtifactLocation {
+                uri: crate::file::path_to_uri(path),
+                uri_base_id: Some(ROOT_ID),
+                index,
+            },
+            None if name.is_absolute() => ArtifactLocation {
+                uri: format!("file://{}", crate::file::path_to_uri(name)),
+                uri_base_id: None,
+                index,
+            },
+            None => ArtifactLocation {
+                uri: crate::file::path_to_uri(name),
+                uri_base_id: None,
+                index,
+            },
+        }
+    }
+}
+
+impl From<Span> for Region {
+    fn from(span: Span) -> Region {
+        let file = span.file.info();
+        let source = &file.source;
+        let column = |offset: usize, col: usize| source[offset - col..offset].chars().count() + 1;
+
+        Region {
+            start_line: span.start.line + 1,
+            start_column: column(span.start.offset, span.start.col),
+            end_line: span.end.line + 1,
+            end_column: column(span.end.offset, span.end.col),
+            byte_offset: span.start.offset,
+            byte_length: span.end.offset - span.start.offset,
+        }
+    }
+}
+
+fn rule_id(code: u16) -> String {
+    format!("{:0>4}", code)
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Bug | Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info | Severity::Help => "note",
+    }
+}
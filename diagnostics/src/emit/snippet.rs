@@ -1,10 +1,17 @@
-use crate::{FileId, Severity, Span};
+use super::RenderOptions;
+use crate::{FileId, LabelStyle, Severity, Span, Suggestion};
+use termcolor::{Color, ColorSpec};
 
 pub struct Snippet<'a> {
     pub severity: Severity,
     pub message: &'a str,
     pub code: Option<u16>,
+    pub primary: Option<Span>,
     pub parts: Vec<SnippetPart<'a>>,
+    pub suggestions: &'a [Suggestion],
+    /// Spanless labels, written as `= note: ...` lines below the excerpt.
+    pub notes: Vec<(Severity, &'a str)>,
+    pub children: Vec<Snippet<'a>>,
 }
 
 pub struct SnippetPart<'a> {
@@ -22,6 +29,7 @@ pub struct Line<'a> {
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Annotation<'a> {
     pub severity: Severity,
+    pub style: LabelStyle,
     pub span: Span,
     pub start: usize,
     pub end: usize,
@@ -40,6 +48,7 @@ pub enum AnnotationKind {
 #[derive(Clone, PartialEq)]
 pub struct MultilineAnnotation<'a> {
     pub severity: Severity,
+    pub style: LabelStyle,
     pub depth: usize,
     pub span: Span,
     pub line_start: usize,
@@ -53,18 +62,30 @@ pub struct MultilineAnnotation<'a> {
 impl Snippet<'_> {
     pub fn finalize(&mut self) {
         for part in &mut self.parts {
-            part.lines.retain(|l| {
-                l.annotations
-                    .iter()
-                    .any(|a| !matches!(a.kind, AnnotationKind::MultiLine(_)))
-            });
-
             for line in &mut part.lines {
                 line.annotations.sort_by_key(|a| a.start);
                 line.annotations.reverse();
             }
         }
     }
+
+    /// The width of the line number gutter, shared with all children so their excerpts line up.
+    pub fn margin(&self) -> usize {
+        let own = self
+            .parts
+            .iter()
+            .flat_map(|p| &p.lines)
+            .map(|l| l.idx + 1)
+            .max()
+            .unwrap_or(1)
+            .to_string()
+            .len();
+
+        self.children
+            .iter()
+            .map(Snippet::margin)
+            .fold(own, usize::max)
+    }
 }
 
 impl SnippetPart<'_> {
@@ -84,6 +105,27 @@ impl SnippetPart<'_> {
     }
 }
 
+impl Annotation<'_> {
+    pub fn color(&self) -> ColorSpec {
+        match self.style {
+            LabelStyle::Primary => self.severity.color(),
+            LabelStyle::Secondary => {
+                let mut spec = ColorSpec::new();
+
+                spec.set_fg(Some(Color::Blue)).set_intense(true);
+                spec
+            }
+        }
+    }
+
+    pub fn marker(&self, options: &RenderOptions) -> char {
+        match self.style {
+            LabelStyle::Primary => options.primary_marker,
+            LabelStyle::Secondary => options.secondary_marker,
+        }
+    }
+}
+
 impl<'a> MultilineAnnotation<'a> {
     /// Compare two `MultilineAnnotation`s considering only the `Span` they cover.
     pub fn same_span(&self, other: &Self) -> bool {
@@ -96,6 +138,7 @@ impl<'a> MultilineAnnotation<'a> {
     pub fn as_start(&self) -> Annotation<'a> {
         Annotation {
             severity: self.severity,
+            style: self.style,
             span: self.span,
             start: self.start_col,
             end: self.start_col + 1,
@@ -107,10 +150,11 @@ impl<'a> MultilineAnnotation<'a> {
     pub fn as_end(&self) -> Annotation<'a> {
         Annotation {
             severity: self.severity,
+            style: self.style,
             span: self.span,
             start: self.end_col.saturating_sub(1),
             end: self.end_col,
-            label: self.label.clone(),
+            label: self.label,
             kind: AnnotationKind::MultiEnd(self.depth),
         }
     }
@@ -118,6 +162,7 @@ impl<'a> MultilineAnnotation<'a> {
     pub fn as_line(&self) -> Annotation<'a> {
         Annotation {
             severity: self.severity,
+            style: self.style,
             span: self.span,
             start: 0,
             end: 0,
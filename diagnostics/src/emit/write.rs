@@ -1,9 +1,30 @@
 use super::snippet::*;
-use crate::FileId;
+use super::RenderOptions;
+use crate::{Diagnostic, FileId, LabelStyle, Severity, Suggestion};
 use termcolor::{Color, ColorSpec, WriteColor};
+use unicode_width::UnicodeWidthChar;
+
+/// The narrowest a cut off source line gets, however little room the terminal has.
+const MIN_WIDTH: usize = 20;
 
 impl Snippet<'_> {
-    pub fn write(&self, mut writer: impl WriteColor) -> std::io::Result<()> {
+    pub fn write(
+        &self,
+        mut writer: impl WriteColor,
+        options: &RenderOptions,
+    ) -> std::io::Result<()> {
+        self.write_section(&mut writer, options, self.margin(), false)
+    }
+
+    /// Writes the diagnostic followed by its children. A child without an excerpt is
+    /// written as a `= note: ...` line, like a spanless label.
+    fn write_section(
+        &self,
+        mut writer: &mut dyn WriteColor,
+        options: &RenderOptions,
+        margin: usize,
+        child: bool,
+    ) -> std::io::Result<()> {
         let mut blue = ColorSpec::new();
         blue.set_fg(Some(Color::Blue)).set_intense(true);
         let code = if let Some(code) = &self.code {
@@ -12,49 +33,191 @@ impl Snippet<'_> {
             String::new()
         };
 
-        writer.set_color(&self.severity.color())?;
-        write!(writer, "{}{}", self.severity.to_string(), code)?;
-        writer.reset()?;
-        write!(writer, ": ")?;
-        writer.set_color(
-            ColorSpec::new()
-                .set_fg(Some(Color::White))
-                .set_intense(true),
-        )?;
-        writeln!(writer, "{}", self.message)?;
+        if child && self.parts.is_empty() {
+            write_note(&mut writer, margin, self.severity, self.message)?;
+        } else {
+            writer.set_color(&self.severity.color())?;
+            write!(writer, "{}{}", self.severity.to_string(), code)?;
+            writer.reset()?;
+            write!(writer, ": ")?;
+            writer.set_color(
+                ColorSpec::new()
+                    .set_fg(Some(Color::White))
+                    .set_intense(true),
+            )?;
+            writeln!(writer, "{}", self.message)?;
+            writer.reset()?;
+        }
 
         for part in &self.parts {
-            let margin = part
-                .lines
-                .iter()
-                .map(|l| l.idx + 1)
-                .max()
-                .unwrap_or(1)
-                .to_string()
-                .len();
+            let name = part.file.info().name.display().to_string();
 
             writer.set_color(&blue)?;
-            write!(writer, "{}-->", " ".repeat(margin))?;
-            writer.reset()?;
-            writeln!(writer, " {}", part.file.name.display())?;
+
+            // Only the excerpt of the primary span gets the `-->` header with its location.
+            match self.primary {
+                Some(span) if span.file == part.file => {
+                    write!(writer, "{}-->", " ".repeat(margin))?;
+                    writer.reset()?;
+                    writeln!(
+                        writer,
+                        " {}:{}:{}",
+                        name,
+                        span.start.line + 1,
+                        span.start.col + 1
+                    )?;
+                }
+                _ => {
+                    write!(writer, "{}:::", " ".repeat(margin))?;
+                    writer.reset()?;
+                    writeln!(writer, " {}", name)?;
+                }
+            }
 
             let max_depth = part.max_depth();
 
             for (i, line) in part.lines.iter().enumerate() {
                 let dots = i + 1 < part.lines.len() && part.lines[i + 1].idx != line.idx + 1;
 
-                line.write(&mut writer, part.file, margin, max_depth, dots)?;
+                line.write(&mut writer, options, part.file, margin, max_depth, dots)?;
             }
         }
 
+        for &(severity, message) in &self.notes {
+            write_note(&mut writer, margin, severity, message)?;
+        }
+
+        for child in &self.children {
+            child.write_section(writer, options, margin, true)?;
+        }
+
+        for suggestion in self.suggestions {
+            let main_file = self.parts.first().map(|p| p.file);
+
+            write_suggestion(&mut writer, suggestion, main_file)?;
+        }
+
         Ok(())
     }
 }
 
+fn write_note(
+    mut writer: impl WriteColor,
+    margin: usize,
+    severity: Severity,
+    message: &str,
+) -> std::io::Result<()> {
+    writer.set_color(ColorSpec::new().set_fg(Some(Color::Blue)).set_intense(true))?;
+    write!(writer, "{} = ", " ".repeat(margin))?;
+    writer.set_color(&severity.color())?;
+    write!(writer, "{}", severity.to_string())?;
+    writer.reset()?;
+    writeln!(writer, ": {}", message)
+}
+
+/// Writes a suggestion as a diff of the lines it touches.
+fn write_suggestion(
+    mut writer: impl WriteColor,
+    suggestion: &Suggestion,
+    main_file: Option<FileId>,
+) -> std::io::Result<()> {
+    let mut blue = ColorSpec::new();
+    blue.set_fg(Some(Color::Blue)).set_intense(true);
+    let mut red = ColorSpec::new();
+    red.set_fg(Some(Color::Red));
+    let mut green = ColorSpec::new();
+    green.set_fg(Some(Color::Green));
+
+    writer.set_color(&Severity::Help.color())?;
+    write!(writer, "help")?;
+    writer.reset()?;
+    writeln!(writer, ": {}", suggestion.message)?;
+
+    let mut files = Vec::<FileId>::new();
+
+    for sub in &suggestion.substitutions {
+        if !files.contains(&sub.span.file) {
+            files.push(sub.span.file);
+        }
+    }
+
+    for file in files {
+        let info = file.info();
+        let mut subs = suggestion
+            .substitutions
+            .iter()
+            .filter(|s| s.span.file == file)
+            .collect::<Vec<_>>();
+
+        subs.sort_by_key(|s| (s.span.start.offset, s.span.end.offset));
+
+        let first = subs[0].span.start.line;
+        let last = subs.iter().map(|s| s.span.end.line).max().unwrap_or(first);
+        let start = info.line_start(first).unwrap_or(0);
+        let end = info.line_start(last).unwrap_or(0) + info.line(last).unwrap_or("").len();
+        let mut patched = String::new();
+        let mut pos = start;
+
+        for sub in subs {
+            if sub.span.start.offset < pos {
+                continue;
+            }
+
+            patched.push_str(&info.source[pos..sub.span.start.offset]);
+            patched.push_str(&sub.replacement);
+            pos = sub.span.end.offset;
+        }
+
+        if pos < end {
+            patched.push_str(&info.source[pos..end]);
+        }
+
+        let margin = (first + patched.lines().count().max(last - first + 1))
+            .to_string()
+            .len();
+
+        writer.set_color(&blue)?;
+
+        if main_file != Some(file) {
+            write!(writer, "{}-->", " ".repeat(margin))?;
+            writer.reset()?;
+            writeln!(writer, " {}", info.name.display())?;
+            writer.set_color(&blue)?;
+        }
+
+        writeln!(writer, "{} |", " ".repeat(margin))?;
+
+        for idx in first..=last {
+            let num = (idx + 1).to_string();
+
+            writer.set_color(&blue)?;
+            write!(writer, "{}{} ", " ".repeat(margin - num.len()), num)?;
+            writer.set_color(&red)?;
+            writeln!(writer, "- {}", info.line(idx).unwrap_or(""))?;
+        }
+
+        for (i, line) in patched.lines().enumerate() {
+            let num = (first + i + 1).to_string();
+
+            writer.set_color(&blue)?;
+            write!(writer, "{}{} ", " ".repeat(margin - num.len()), num)?;
+            writer.set_color(&green)?;
+            writeln!(writer, "+ {}", line)?;
+        }
+
+        writer.set_color(&blue)?;
+        writeln!(writer, "{} |", " ".repeat(margin))?;
+        writer.reset()?;
+    }
+
+    Ok(())
+}
+
 impl Line<'_> {
     pub fn write(
         &self,
         mut writer: impl WriteColor,
+        options: &RenderOptions,
         file: FileId,
         margin: usize,
         max_depth: usize,
@@ -63,6 +226,48 @@ impl Line<'_> {
         let mut blue = ColorSpec::new();
         blue.set_fg(Some(Color::Blue)).set_intense(true);
         let num = (self.idx + 1).to_string();
+        let info = file.info();
+        let text = DisplayLine {
+            text: info.line(self.idx).unwrap_or(""),
+            tab_width: options.tab_width,
+        };
+        let gutter = if max_depth == 0 { 0 } else { max_depth + 1 };
+        let (lo, hi) = match options.width {
+            Some(width) => self.window(&text, width.saturating_sub(margin + 3 + gutter)),
+            None => (0, usize::MAX),
+        };
+        // The display columns that show source text rather than the `...` of cut off parts.
+        let visible = (
+            if lo > 0 { lo + 3 } else { 0 },
+            if hi == usize::MAX {
+                hi
+            } else {
+                hi.saturating_sub(3)
+            },
+        );
+        // Annotations outside the visible columns get no markers, rather than markers that
+        // point at the `...`.
+        let clip = |ann: &Annotation| {
+            let start = text.col(ann.start);
+            let end = text.col(ann.end).max(start + 1);
+
+            if end <= visible.0 || start >= visible.1 {
+                return None;
+            }
+
+            let start = start.max(visible.0) - lo;
+
+            Some((start, (end.min(visible.1) - lo).max(start + 1)))
+        };
+        let pointer = |ann: &Annotation| {
+            clip(ann).map_or(String::new(), |(start, end)| {
+                format!(
+                    "{}{}",
+                    options.glyph('─').to_string().repeat(start),
+                    ann.marker(options).to_string().repeat(end - start)
+                )
+            })
+        };
 
         writer.set_color(&blue)?;
         write!(writer, "{}{} | ", " ".repeat(margin - num.len()), num)?;
@@ -73,18 +278,18 @@ impl Line<'_> {
         for ann in &self.annotations {
             if let AnnotationKind::MultiStart(x) = ann.kind {
                 if ann.start == 0 {
-                    before[x - 1] = ('╭', ann.severity.color());
+                    before[x - 1] = ('╭', ann.color());
                 }
             } else if let AnnotationKind::MultiLine(x) = ann.kind {
-                before[x - 1] = ('│', ann.severity.color());
+                before[x - 1] = ('│', ann.color());
             } else if let AnnotationKind::MultiEnd(x) = ann.kind {
-                before[x - 1] = ('│', ann.severity.color());
+                before[x - 1] = ('│', ann.color());
             }
         }
 
         for (sym, color) in &before {
             writer.set_color(color)?;
-            write!(writer, "{}", sym)?;
+            write!(writer, "{}", options.glyph(*sym))?;
         }
 
         if !before.is_empty() {
@@ -92,49 +297,35 @@ impl Line<'_> {
             write!(writer, " ")?;
         }
 
-        writeln!(writer, "{}", file.source.lines().nth(self.idx).unwrap())?;
-
-        for ann in &self.annotations {
-            let label = if let Some(lbl) = ann.label { lbl } else { "" };
-
-            match ann.kind {
-                AnnotationKind::Single => {
-                    writer.set_color(&blue)?;
-                    write!(writer, "{} | ", " ".repeat(margin))?;
+        writeln!(writer, "{}", text.render(lo, hi))?;
 
-                    for (sym, color) in &before {
-                        writer.set_color(color)?;
-                        write!(writer, "{}", sym)?;
-                    }
+        let singles = self
+            .annotations
+            .iter()
+            .filter(|a| a.kind == AnnotationKind::Single)
+            .filter_map(|a| Some((a, clip(a)?)))
+            .collect::<Vec<_>>();
 
-                    writer.set_color(&ann.severity.color())?;
+        if !singles.is_empty() {
+            write_singles(&mut writer, options, margin, &before, &singles)?;
+        }
 
-                    if !before.is_empty() {
-                        write!(writer, " ")?;
-                    }
+        for ann in &self.annotations {
+            let label = ann.label.unwrap_or("");
 
-                    write!(
-                        writer,
-                        "{}{} {}",
-                        " ".repeat(ann.start),
-                        ann.severity.symbol().repeat(ann.end - ann.start),
-                        label
-                    )?;
-                    writer.reset()?;
-                    writeln!(writer)?;
-                }
+            match ann.kind {
+                AnnotationKind::Single => {}
                 AnnotationKind::MultiStart(x) => {
                     if ann.start != 0 {
                         writer.set_color(&blue)?;
                         write!(writer, "{} | ", " ".repeat(margin))?;
-                        before[x - 1] = ('┌', ann.severity.color());
+                        before[x - 1] = ('┌', ann.color());
 
                         let mut repl = Vec::new();
 
-                        for i in x..before.len() {
-                            if before[i].0 == ' ' {
-                                let old =
-                                    std::mem::replace(&mut before[i], ('─', ann.severity.color()));
+                        for (i, b) in before.iter_mut().enumerate().skip(x) {
+                            if b.0 == ' ' {
+                                let old = std::mem::replace(b, ('─', ann.color()));
 
                                 repl.push((i, old));
                             }
@@ -142,24 +333,18 @@ impl Line<'_> {
 
                         for (sym, color) in &before {
                             writer.set_color(color)?;
-                            write!(writer, "{}", sym)?;
+                            write!(writer, "{}", options.glyph(*sym))?;
                         }
 
-                        writer.set_color(&ann.severity.color())?;
-                        write!(writer, "─")?;
+                        writer.set_color(&ann.color())?;
+                        write!(writer, "{}", options.glyph('─'))?;
                         before[x - 1].0 = '│';
 
                         for (i, old) in repl {
                             before[i] = old;
                         }
 
-                        write!(
-                            writer,
-                            "{}{} {}",
-                            "─".repeat(ann.start),
-                            ann.severity.symbol().repeat(ann.end - ann.start),
-                            label
-                        )?;
+                        write!(writer, "{} {}", pointer(ann), label)?;
                         writer.reset()?;
                         writeln!(writer)?;
                     } else {
@@ -174,10 +359,9 @@ impl Line<'_> {
 
                     let mut repl = Vec::new();
 
-                    for i in x..before.len() {
-                        if before[i].0 == ' ' {
-                            let old =
-                                std::mem::replace(&mut before[i], ('─', ann.severity.color()));
+                    for (i, b) in before.iter_mut().enumerate().skip(x) {
+                        if b.0 == ' ' {
+                            let old = std::mem::replace(b, ('─', ann.color()));
 
                             repl.push((i, old));
                         }
@@ -185,24 +369,18 @@ impl Line<'_> {
 
                     for (sym, color) in &before {
                         writer.set_color(color)?;
-                        write!(writer, "{}", sym)?;
+                        write!(writer, "{}", options.glyph(*sym))?;
                     }
 
-                    writer.set_color(&ann.severity.color())?;
-                    write!(writer, "─")?;
+                    writer.set_color(&ann.color())?;
+                    write!(writer, "{}", options.glyph('─'))?;
                     before[x - 1] = (' ', ColorSpec::new());
 
                     for (i, old) in repl {
                         before[i] = old;
                     }
 
-                    write!(
-                        writer,
-                        "{}{} {}",
-                        "─".repeat(ann.start),
-                        ann.severity.symbol().repeat(ann.end - ann.start),
-                        label
-                    )?;
+                    write!(writer, "{} {}", pointer(ann), label)?;
                     writer.reset()?;
                     writeln!(writer)?;
                 }
@@ -217,7 +395,7 @@ impl Line<'_> {
 
             for (sym, color) in &before {
                 writer.set_color(color)?;
-                write!(writer, "{}", sym)?;
+                write!(writer, "{}", options.glyph(*sym))?;
             }
 
             writer.reset()?;
@@ -227,3 +405,332 @@ impl Line<'_> {
         Ok(())
     }
 }
+
+/// Draws the markers of all single-line annotations of a line on one row, followed by rows
+/// that connect each label to its marker with a vertical bar:
+///
+/// ```text
+/// let x: i32 = "hello" + foo;
+///        ---   ^^^^^^^   --- not a number
+///        |     |
+///        |     expected `i32`
+///        declared here
+/// ```
+///
+/// `annotations` are sorted from right to left, with their display columns.
+fn write_singles(
+    mut writer: impl WriteColor,
+    options: &RenderOptions,
+    margin: usize,
+    before: &[(char, ColorSpec)],
+    annotations: &[(&Annotation, (usize, usize))],
+) -> std::io::Result<()> {
+    let width = annotations
+        .iter()
+        .map(|(_, (_, end))| *end)
+        .max()
+        .unwrap_or(0);
+    let mut markers = vec![(' ', ColorSpec::new()); width];
+    let mut by_style = annotations.to_vec();
+
+    // Primary markers are drawn last so that they are not hidden by overlapping ones.
+    by_style.sort_by_key(|(ann, _)| std::cmp::Reverse(ann.style));
+
+    for (ann, (start, end)) in by_style {
+        for cell in &mut markers[start..end] {
+            *cell = (ann.marker(options), ann.color());
+        }
+    }
+
+    let labelled = annotations
+        .iter()
+        .filter(|(ann, _)| ann.label.is_some_and(|l| !l.is_empty()))
+        .collect::<Vec<_>>();
+
+    // The rightmost label is written next to the markers unless other markers reach past it.
+    let inline = labelled.first().filter(|(_, (_, end))| {
+        annotations
+            .iter()
+            .all(|(_, (_, other_end))| other_end <= end)
+    });
+    let below = &labelled[inline.is_some() as usize..];
+
+    write_row(&mut writer, options, margin, before, &markers)?;
+
+    if let Some((ann, _)) = inline {
+        writer.set_color(&ann.color())?;
+        write!(writer, " {}", ann.label.unwrap_or(""))?;
+        writer.reset()?;
+    }
+
+    writeln!(writer)?;
+
+    if below.is_empty() {
+        return Ok(());
+    }
+
+    let connector = |ann: &Annotation| (options.glyph('│'), ann.color());
+    let mut row = vec![(' ', ColorSpec::new()); width];
+
+    for (ann, (start, _)) in below {
+        row[*start] = connector(ann);
+    }
+
+    write_row(&mut writer, options, margin, before, &row)?;
+    writeln!(writer)?;
+
+    for (i, (ann, (start, _))) in below.iter().enumerate() {
+        let mut row = vec![(' ', ColorSpec::new()); *start];
+
+        for (other, (other_start, _)) in &below[i + 1..] {
+            if other_start < start {
+                row[*other_start] = connector(other);
+            }
+        }
+
+        let written = write_row(&mut writer, options, margin, before, &row)?;
+
+        writer.set_color(&ann.color())?;
+        write!(
+            writer,
+            "{}{}",
+            " ".repeat(start - written),
+            ann.label.unwrap_or("")
+        )?;
+        writer.reset()?;
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the gutter of an annotation row followed by `cells` without trailing spaces or a
+/// line break, returning the number of cells written.
+fn write_row(
+    mut writer: impl WriteColor,
+    options: &RenderOptions,
+    margin: usize,
+    before: &[(char, ColorSpec)],
+    cells: &[(char, ColorSpec)],
+) -> std::io::Result<usize> {
+    writer.set_color(ColorSpec::new().set_fg(Some(Color::Blue)).set_intense(true))?;
+    write!(writer, "{} | ", " ".repeat(margin))?;
+
+    for (sym, color) in before {
+        writer.set_color(color)?;
+        write!(writer, "{}", options.glyph(*sym))?;
+    }
+
+    if !before.is_empty() {
+        write!(writer, " ")?;
+    }
+
+    let len = cells
+        .iter()
+        .rposition(|(c, _)| *c != ' ')
+        .map_or(0, |i| i + 1);
+
+    for (c, color) in &cells[..len] {
+        writer.set_color(color)?;
+        write!(writer, "{}", c)?;
+    }
+
+    writer.reset()?;
+    Ok(len)
+}
+
+impl Line<'_> {
+    /// The display columns `lo..hi` of `text` that fit in `width`, chosen to keep the
+    /// annotations in view. `hi` is `usize::MAX` if the line is not cut off at the end.
+    fn window(&self, text: &DisplayLine, width: usize) -> (usize, usize) {
+        let width = width.max(MIN_WIDTH);
+        let len = text.width();
+
+        if len <= width {
+            return (0, usize::MAX);
+        }
+
+        // The columns left for source text when both ends are cut off with `...`.
+        let room = width - 6;
+        let extent = |primary_only: bool| {
+            let columns = self
+                .annotations
+                .iter()
+                .filter(|a| !matches!(a.kind, AnnotationKind::MultiLine(_)))
+                .filter(|a| !primary_only || a.style == LabelStyle::Primary)
+                .map(|a| (text.col(a.start), text.col(a.end)));
+            let start = columns.clone().map(|(start, _)| start).min()?;
+            let end = columns.map(|(_, end)| end).max()?.max(start);
+
+            Some((start, end))
+        };
+        // If the annotations are too far apart, the primary ones are kept in view.
+        let (start, end) = match extent(false) {
+            Some((start, end)) if end - start > room => extent(true).unwrap_or((start, end)),
+            extent => extent.unwrap_or((0, 0)),
+        };
+        let lo = start
+            .saturating_sub(3 + room.saturating_sub(end - start) / 2)
+            .min(len - width);
+
+        if lo + width >= len {
+            (lo, usize::MAX)
+        } else {
+            (lo, lo + width)
+        }
+    }
+}
+
+/// A source line as it appears on screen, with tabs expanded and wide characters taking up
+/// two columns.
+struct DisplayLine<'a> {
+    text: &'a str,
+    tab_width: usize,
+}
+
+impl DisplayLine<'_> {
+    fn char_width(&self, c: char) -> usize {
+        if c == '\t' {
+            self.tab_width
+        } else {
+            c.width().unwrap_or(0)
+        }
+    }
+
+    /// The display column of the byte column `byte`. Columns past the end of the line,
+    /// such as the line break, are one column wide.
+    fn col(&self, byte: usize) -> usize {
+        let mut end = byte.min(self.text.len());
+
+        while !self.text.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let prefix: usize = self.text[..end].chars().map(|c| self.char_width(c)).sum();
+
+        prefix + byte.saturating_sub(self.text.len())
+    }
+
+    fn width(&self) -> usize {
+        self.col(self.text.len())
+    }
+
+    /// Renders the display columns `lo..hi`, replacing the parts that are cut off with `...`.
+    /// Characters that are only partly visible are replaced with spaces so that every
+    /// column stays where it is.
+    fn render(&self, lo: usize, hi: usize) -> String {
+        let mut rendered = String::new();
+        let start = if lo > 0 { lo + 3 } else { 0 };
+        let end = if hi == usize::MAX {
+            usize::MAX
+        } else {
+            hi.saturating_sub(3).max(start)
+        };
+        let mut col = 0;
+
+        if lo > 0 {
+            rendered.push_str("...");
+        }
+
+        for c in self.text.chars() {
+            let (a, b) = (col, col + self.char_width(c));
+
+            col = b;
+
+            if a < start && b <= start {
+                continue;
+            } else if a >= end {
+                break;
+            } else if a < start || b > end || c == '\t' {
+                rendered.push_str(&" ".repeat(b.min(end) - a.max(start)));
+            } else {
+                rendered.push(c);
+            }
+        }
+
+        if hi != usize::MAX {
+            rendered.push_str("...");
+        }
+
+        rendered
+    }
+}
+
+pub fn write_explain_hint(mut writer: impl WriteColor, code: u16) -> std::io::Result<()> {
+    writer.set_color(ColorSpec::new().set_bold(true))?;
+    write!(
+        writer,
+        "For more information about this error, try `--explain {:0>4}`.",
+        code
+    )?;
+    writer.reset()?;
+    writeln!(writer)
+}
+
+/// Writes `file:line:col: severity[code]: message`, optionally followed by a line of the
+/// same shape for every other label and every child.
+///
+/// Labels are written as `note`s whatever their severity, so that tools counting errors by
+/// line see each diagnostic once.
+pub fn write_short(
+    diag: &Diagnostic,
+    mut writer: impl WriteColor,
+    labels: bool,
+) -> std::io::Result<()> {
+    let code = if let Some(code) = &diag.code {
+        format!("[{:0>4}]", code)
+    } else {
+        String::new()
+    };
+
+    let primary = diag.primary_span();
+    let location = |span: Option<crate::Span>| match span {
+        Some(span) => format!(
+            "{}:{}:{}: ",
+            span.file.info().name.display(),
+            span.start.line + 1,
+            span.start.col + 1
+        ),
+        None => String::new(),
+    };
+
+    write!(writer, "{}", location(primary))?;
+    writer.set_color(&diag.severity.color())?;
+    write!(writer, "{}{}", diag.severity.to_string(), code)?;
+    writer.reset()?;
+    writeln!(writer, ": {}", diag.message)?;
+
+    if !labels {
+        return Ok(());
+    }
+
+    let mut skipped_primary = false;
+
+    for label in &diag.labels {
+        if label.span.is_some() && label.span == primary && !skipped_primary {
+            skipped_primary = true;
+            continue;
+        }
+
+        let message = match &label.message {
+            Some(message) => message,
+            None => continue,
+        };
+
+        write!(writer, "{}", location(label.span.or(primary)))?;
+        writer.set_color(&Severity::Info.color())?;
+        write!(writer, "note")?;
+        writer.reset()?;
+        writeln!(writer, ": {}", message)?;
+    }
+
+    for child in &diag.children {
+        write!(writer, "{}", location(child.primary_span().or(primary)))?;
+        writer.set_color(&child.severity.color())?;
+        write!(writer, "{}", child.severity.to_string())?;
+        writer.reset()?;
+        writeln!(writer, ": {}", child.message)?;
+    }
+
+    Ok(())
+}
@@ -1,6 +1,14 @@
-use std::path::PathBuf;
+use crate::{ExpnData, Position};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
 
-static mut FILE_INTERNER: FileInterner = FileInterner::new();
+static SOURCE_MAP: OnceLock<SourceMap> = OnceLock::new();
+
+/// The generation handed to the next new or cleared `SourceMap`. Generation 0 belongs to the
+/// global map, so that `FileId::default()` refers to its first file.
+static NEXT_GENERATION: AtomicU32 = AtomicU32::new(1);
 
 #[derive(
     Debug,
@@ -15,49 +23,252 @@ static mut FILE_INTERNER: FileInterner = FileInterner::new();
     serde::Serialize,
     serde::Deserialize,
 )]
-pub struct FileId(usize);
+pub struct FileId {
+    index: u32,
+    /// Identifies the `SourceMap` that handed out this id, and how often it had been cleared.
+    generation: u32,
+}
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct FileInfo {
     pub name: PathBuf,
     pub source: String,
+    /// Byte offset at which each line starts.
+    lines: Vec<usize>,
+}
+
+/// A table of source files that `FileId`s index into.
+///
+/// Files are interned by name and may be added and looked up from any thread. `FileId::new`,
+/// `FileId::info` and the emitters use the process-wide map returned by `global`, but tools and
+/// tests can create maps of their own and look files up with `get`. A map never resolves ids
+/// that another map handed out. The map also holds the macro expansions that `ExpnId`s index
+/// into.
+pub struct SourceMap {
+    files: RwLock<Files>,
+    pub(crate) expansions: RwLock<Expansions>,
+}
+
+pub(crate) struct Expansions {
+    pub(crate) generation: u32,
+    pub(crate) data: Vec<Arc<ExpnData>>,
+}
+
+struct Files {
+    generation: u32,
+    infos: Vec<Arc<FileInfo>>,
+    names: HashMap<PathBuf, u32>,
 }
 
 impl FileId {
     pub fn new(name: impl Into<PathBuf>, source: impl Into<String>) -> Self {
-        unsafe {
-            FILE_INTERNER.intern(FileInfo {
-                name: name.into(),
-                source: source.into(),
-            })
+        SourceMap::global().add(name, source)
+    }
+
+    /// Looks up this file in the global `SourceMap`.
+    ///
+    /// # Panics
+    /// Panics if this id was handed out by another `SourceMap`, or by the global one before it
+    /// was cleared.
+    pub fn info(self) -> Arc<FileInfo> {
+        SourceMap::global()
+            .get(self)
+            .expect("FileId does not belong to the source map")
+    }
+}
+
+impl Files {
+    fn id(&self, idx: usize) -> FileId {
+        FileId {
+            index: idx as u32,
+            generation: self.generation,
         }
     }
 }
 
-impl std::ops::Deref for FileId {
-    type Target = FileInfo;
+impl FileInfo {
+    pub fn new(name: impl Into<PathBuf>, source: impl Into<String>) -> Self {
+        let source = source.into();
+        let lines = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
 
-    fn deref(&self) -> &Self::Target {
-        unsafe { &FILE_INTERNER.data[self.0] }
+        FileInfo {
+            name: name.into(),
+            source,
+            lines,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
     }
-}
 
-struct FileInterner {
-    data: Vec<FileInfo>,
+    /// Returns the byte offset at which `line` starts.
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.lines.get(line).copied()
+    }
+
+    /// Returns the text of `line` without its line terminator.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let start = self.line_start(line)?;
+        let end = self.line_start(line + 1).unwrap_or(self.source.len());
+        let text = &self.source[start..end];
+        let text = text.strip_suffix('\n').unwrap_or(text);
+
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    /// Returns the line and byte column of `offset`.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let line = self.lines.partition_point(|&start| start <= offset) - 1;
+
+        (line, offset - self.lines[line])
+    }
+
+    /// Returns the offset of byte column `col` in `line`, which may point at the line terminator
+    /// but not past it.
+    pub fn offset(&self, line: usize, col: usize) -> Option<usize> {
+        let start = self.line_start(line)?;
+
+        if col <= self.line(line)?.len() {
+            Some(start + col)
+        } else {
+            None
+        }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let (line, col) = self.line_col(offset);
+
+        Position {
+            offset: offset.min(self.source.len()),
+            line,
+            col,
+        }
+    }
 }
 
-impl FileInterner {
-    const fn new() -> Self {
-        FileInterner { data: Vec::new() }
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::with_generation(next_generation())
     }
 
-    fn intern(&mut self, value: FileInfo) -> FileId {
-        if let Some(idx) = self.data.iter().position(|f| f.name == value.name) {
-            FileId(idx)
+    fn with_generation(generation: u32) -> Self {
+        SourceMap {
+            files: RwLock::new(Files {
+                generation,
+                infos: Vec::new(),
+                names: HashMap::new(),
+            }),
+            expansions: RwLock::new(Expansions {
+                generation,
+                data: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn global() -> &'static SourceMap {
+        SOURCE_MAP.get_or_init(|| SourceMap::with_generation(0))
+    }
+
+    /// Adds a file, or returns the id of the file that was already added under `name`.
+    pub fn add(&self, name: impl Into<PathBuf>, source: impl Into<String>) -> FileId {
+        let name = name.into();
+
+        if let Some(file) = self.find(&name) {
+            return file;
+        }
+
+        // Index the lines before taking the write lock, so other threads are not held up.
+        let info = Arc::new(FileInfo::new(name, source));
+        let mut files = self.files.write().unwrap();
+        let Files { infos, names, .. } = &mut *files;
+        let idx = *names.entry(info.name.clone()).or_insert_with(|| {
+            infos.push(info);
+            infos.len() as u32 - 1
+        });
+
+        files.id(idx as usize)
+    }
+
+    /// Returns `None` if `file` was handed out by another map or before the last `clear`.
+    pub fn get(&self, file: FileId) -> Option<Arc<FileInfo>> {
+        let files = self.files.read().unwrap();
+
+        if file.generation == files.generation {
+            files.infos.get(file.index as usize).cloned()
         } else {
-            self.data.push(value);
+            None
+        }
+    }
+
+    pub fn find(&self, name: impl AsRef<Path>) -> Option<FileId> {
+        let name = name.as_ref();
+        let files = self.files.read().unwrap();
+
+        files.names.get(name).map(|&idx| files.id(idx as usize))
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.read().unwrap().infos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-            FileId(self.data.len() - 1)
+    /// Removes every file and expansion, invalidating all `FileId`s and `ExpnId`s handed out
+    /// by this map so far.
+    ///
+    /// Clearing the global map also invalidates the ids of other threads, such as tests running
+    /// in parallel, whose `FileId::info` calls then panic. Such tests should use maps of their
+    /// own instead.
+    pub fn clear(&self) {
+        let generation = next_generation();
+        let mut files = self.files.write().unwrap();
+        let mut expansions = self.expansions.write().unwrap();
+
+        files.generation = generation;
+        files.infos.clear();
+        files.names.clear();
+        expansions.generation = generation;
+        expansions.data.clear();
+    }
+}
+
+impl Default for SourceMap {
+    fn default() -> Self {
+        SourceMap::new()
+    }
+}
+
+fn next_generation() -> u32 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Percent-encodes `path` for use in a URI, using `/` as the separator.
+pub(crate) fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::new();
+
+    for (i, comp) in path.components().enumerate() {
+        let comp = comp.as_os_str().to_string_lossy();
+
+        if i > 0 && !uri.ends_with('/') {
+            uri.push('/');
+        }
+
+        for b in comp.bytes() {
+            match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    uri.push(b as char)
+                }
+                b'\\' => uri.push('/'),
+                _ => uri.push_str(&format!("%{:02X}", b)),
+            }
         }
     }
+
+    uri
 }
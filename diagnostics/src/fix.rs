@@ -0,0 +1,579 @@
+use crate::{Applicability, Diagnostic, FileId, Suggestion};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+const CONTEXT: usize = 3;
+
+/// A single replacement of the bytes `start..end` of a file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Edit {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+/// The machine-applicable suggestions of a set of diagnostics, resolved into
+/// non-overlapping edits per file.
+///
+/// Suggestions are considered in source order and applied as a whole: when any part of a
+/// suggestion overlaps an edit that was already accepted, the entire suggestion is skipped.
+#[derive(Debug, Default, Clone)]
+pub struct Fixes {
+    edits: BTreeMap<FileId, Vec<Edit>>,
+    skipped: usize,
+}
+
+impl Fixes {
+    pub fn new<'a>(diagnostics: impl IntoIterator<Item = &'a Diagnostic>) -> Fixes {
+        let mut suggestions = diagnostics
+            .into_iter()
+            .flat_map(all_suggestions)
+            .filter(|s| s.applicability == Applicability::MachineApplicable)
+            .filter(|s| !s.substitutions.is_empty())
+            .map(|s| (sort_key(s), s))
+            .collect::<Vec<_>>();
+
+        suggestions.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut fixes = Fixes::default();
+        let mut seen = Vec::new();
+
+        for (key, suggestion) in suggestions {
+            if seen.contains(&key) {
+                continue;
+            }
+
+            if fixes.accepts(suggestion) {
+                for sub in &suggestion.substitutions {
+                    let edits = fixes.edits.entry(sub.span.file).or_default();
+
+                    edits.push(Edit {
+                        start: sub.span.start.offset,
+                        end: sub.span.end.offset,
+                        replacement: sub.replacement.clone(),
+                    });
+                    edits.sort();
+                }
+            } else {
+                fixes.skipped += 1;
+            }
+
+            seen.push(key);
+        }
+
+        fixes
+    }
+
+    fn accepts(&self, suggestion: &Suggestion) -> bool {
+        suggestion.substitutions.iter().enumerate().all(|(i, sub)| {
+            let (start, end) = (sub.span.start.offset, sub.span.end.offset);
+            let accepted = self.edits.get(&sub.span.file).into_iter().flatten();
+            let siblings = suggestion.substitutions[..i]
+                .iter()
+                .filter(|s| s.span.file == sub.span.file)
+                .map(|s| (s.span.start.offset, s.span.end.offset));
+
+            accepted
+                .map(|e| (e.start, e.end))
+                .chain(siblings)
+                .all(|other| !overlaps((start, end), other))
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// The number of suggestions that were dropped because they overlapped another one.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = FileId> + '_ {
+        self.edits.keys().copied()
+    }
+
+    pub fn edits(&self, file: FileId) -> &[Edit] {
+        self.edits.get(&file).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the patched source of `file`, or `None` if there is nothing to fix in it.
+    pub fn apply(&self, file: FileId) -> Option<String> {
+        let edits = self.edits.get(&file)?;
+        let info = file.info();
+
+        Some(apply_edits(&info.source, 0, edits))
+    }
+
+    /// Rewrites every affected file on disk.
+    pub fn write_files(&self) -> std::io::Result<()> {
+        for file in self.files() {
+            if let Some(patched) = self.apply(file) {
+                std::fs::write(&file.info().name, patched)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the changes as a unified diff without touching any file.
+    pub fn write_diff(&self, mut writer: impl Write) -> std::io::Result<()> {
+        for (&file, edits) in &self.edits {
+            let info = file.info();
+            let name = info.name.display();
+
+            writeln!(writer, "--- a/{}", name)?;
+            writeln!(writer, "+++ b/{}", name)?;
+
+            for hunk in hunks(&info.source, edits) {
+                hunk.write(&mut writer)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The suggestions of a diagnostic and of all its children.
+fn all_suggestions(diag: &Diagnostic) -> Vec<&Suggestion> {
+    let mut suggestions = diag.suggestions.iter().collect::<Vec<_>>();
+
+    for child in &diag.children {
+        suggestions.extend(all_suggestions(child));
+    }
+
+    suggestions
+}
+
+fn sort_key(suggestion: &Suggestion) -> Vec<(FileId, usize, usize, &str)> {
+    let mut key = suggestion
+        .substitutions
+        .iter()
+        .map(|s| {
+            (
+                s.span.file,
+                s.span.start.offset,
+                s.span.end.offset,
+                s.replacement.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    key.sort();
+    key
+}
+
+/// Two edits conflict when their ranges intersect or when both insert at the same offset,
+/// since the order of the inserted texts would be ambiguous.
+fn overlaps(a: (usize, usize), b: (usize, usize)) -> bool {
+    (a.0 < b.1 && b.0 < a.1) || a.0 == b.0
+}
+
+/// Applies sorted, non-overlapping `edits` to `text`, which starts at byte `base` of its file.
+fn apply_edits<'a>(text: &str, base: usize, edits: impl IntoIterator<Item = &'a Edit>) -> String {
+    let mut patched = String::with_capacity(text.len());
+    let mut pos = 0;
+
+    for edit in edits {
+        patched.push_str(&text[pos..edit.start - base]);
+        patched.push_str(&edit.replacement);
+        pos = edit.end - base;
+    }
+
+    patched.push_str(&text[pos..]);
+    patched
+}
+
+struct Hunk<'a> {
+    old_start: usize,
+    new_start: usize,
+    lines: Vec<(char, Cow<'a, str>)>,
+}
+
+/// Groups the edits of a file into unified diff hunks with `CONTEXT` lines around each change.
+fn hunks<'a>(source: &'a str, edits: &[Edit]) -> Vec<Hunk<'a>> {
+    let lines = source.split_inclusive('\n').collect::<Vec<_>>();
+    let mut starts = Vec::with_capacity(lines.len() + 1);
+    let mut offset = 0;
+
+    for line in &lines {
+        starts.push(offset);
+        offset += line.len();
+    }
+
+    starts.push(offset);
+
+    let line_of = |offset: usize| {
+        starts
+            .partition_point(|&s| s <= offset)
+            .saturating_sub(1)
+            .min(lines.len().saturating_sub(1))
+    };
+
+    // Edits that touch the same lines are turned into a single chunk of replaced lines.
+    let mut chunks: Vec<(usize, usize, Vec<&Edit>)> = Vec::new();
+
+    for edit in edits {
+        let first = line_of(edit.start);
+        let last = line_of(edit.end.saturating_sub(1).max(edit.start));
+
+        match chunks.last_mut() {
+            Some((_, end, group)) if first <= *end => {
+                *end = (*end).max(last);
+                group.push(edit);
+            }
+            _ => chunks.push((first, last, vec![edit])),
+        }
+    }
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut delta = 0isize;
+    let mut prev_end = 0;
+
+    for (first, last, group) in chunks {
+        let lo = starts[first];
+        let hi = starts[(last + 1).min(lines.len())];
+        let old = &lines[first..(last + 1).min(lines.len())];
+        let new = apply_edits(&source[lo..hi], lo, group);
+        let new = new
+            .split_inclusive('\n')
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let context_start = first.saturating_sub(CONTEXT);
+
+        let hunk = match hunks.last_mut() {
+            Some(hunk) if first <= prev_end + 2 * CONTEXT => {
+                for line in &lines[prev_end..first] {
+                    hunk.lines.push((' ', Cow::Borrowed(*line)));
+                }
+
+                hunk
+            }
+            _ => {
+                if let Some(hunk) = hunks.last_mut() {
+                    let end = (prev_end + CONTEXT).min(lines.len());
+
+                    for line in &lines[prev_end..end] {
+                        hunk.lines.push((' ', Cow::Borrowed(*line)));
+                    }
+                }
+
+                hunks.push(Hunk {
+                    old_start: context_start,
+                    new_start: (context_start as isize + delta) as usize,
+                    lines: lines[context_start..first]
+                        .iter()
+                        .map(|l| (' ', Cow::Borrowed(*l)))
+                        .collect(),
+                });
+
+                hunks.last_mut().unwrap()
+            }
+        };
+
+        for line in old {
+            hunk.lines.push(('-', Cow::Borrowed(*line)));
+        }
+
+        delta += new.len() as isize - old.len() as isize;
+
+        for line in new {
+            hunk.lines.push(('+', Cow::Owned(line)));
+        }
+
+        prev_end = (last + 1).min(lines.len());
+    }
+
+    if let Some(hunk) = hunks.last_mut() {
+        let end = (prev_end + CONTEXT).min(lines.len());
+
+        for line in &lines[prev_end..end] {
+            hunk.lines.push((' ', Cow::Borrowed(*line)));
+        }
+    }
+
+    hunks
+}
+
+impl Hunk<'_> {
+    fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        let old_len = self.lines.iter().filter(|(k, _)| *k != '+').count();
+        let new_len = self.lines.iter().filter(|(k, _)| *k != '-').count();
+
+        writeln!(
+            writer,
+            "@@ -{} +{} @@",
+            range(self.old_start, old_len),
+            range(self.new_start, new_len)
+        )?;
+
+        for (kind, line) in &self.lines {
+            match line.strip_suffix('\n') {
+                Some(line) => writeln!(writer, "{}{}", kind, line)?,
+                None => {
+                    writeln!(writer, "{}{}", kind, line)?;
+                    writeln!(writer, "\\ No newline at end of file")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompactSpan, Severity};
+
+    fn error() -> Diagnostic {
+        Diagnostic::new(Severity::Error, None, "error")
+    }
+
+    fn part(file: FileId, lo: usize, hi: usize, replacement: &str) -> (crate::Span, String) {
+        (
+            CompactSpan::new(file, lo, hi).into(),
+            replacement.to_string(),
+        )
+    }
+
+    fn fix(parts: Vec<(crate::Span, String)>) -> Diagnostic {
+        error().multipart_suggestion(parts, "fix", Applicability::MachineApplicable)
+    }
+
+    fn diff(fixes: &Fixes) -> String {
+        let mut diff = Vec::new();
+
+        fixes.write_diff(&mut diff).unwrap();
+        String::from_utf8(diff).unwrap()
+    }
+
+    /// A file with the lines `1` to `20`, returning it with the offset of each line.
+    fn numbered(name: &str) -> (FileId, Vec<usize>) {
+        let source = (1..=20).map(|n| format!("{}\n", n)).collect::<String>();
+        let offsets = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        (FileId::new(name, source), offsets)
+    }
+
+    #[test]
+    fn overlapping_suggestions_keep_the_first() {
+        let file = FileId::new("fix/overlap.rs", "let x = 1;\n");
+        let diagnostics = [
+            fix(vec![part(file, 4, 9, "y = 2")]),
+            fix(vec![part(file, 4, 5, "z")]),
+            fix(vec![part(file, 8, 9, "3")]),
+        ];
+        let fixes = Fixes::new(&diagnostics);
+
+        assert_eq!(fixes.skipped(), 1);
+        assert_eq!(fixes.apply(file).unwrap(), "let z = 3;\n");
+    }
+
+    #[test]
+    fn insertions_at_the_same_offset_conflict() {
+        let file = FileId::new("fix/insert.rs", "x\n");
+        let diagnostics = [
+            fix(vec![part(file, 0, 0, "b")]),
+            fix(vec![part(file, 0, 0, "a")]),
+            fix(vec![part(file, 0, 1, "z")]),
+            fix(vec![part(file, 1, 1, "c")]),
+        ];
+        let fixes = Fixes::new(&diagnostics);
+
+        assert_eq!(fixes.skipped(), 2);
+        assert_eq!(fixes.apply(file).unwrap(), "axc\n");
+    }
+
+    #[test]
+    fn edits_next_to_each_other_are_applied() {
+        let file = FileId::new("fix/adjacent.rs", "abc\n");
+        let diagnostics = [
+            fix(vec![part(file, 0, 1, "x")]),
+            fix(vec![part(file, 1, 3, "z")]),
+            fix(vec![part(file, 3, 3, "y")]),
+        ];
+        let fixes = Fixes::new(&diagnostics);
+
+        assert_eq!(fixes.skipped(), 0);
+        assert_eq!(fixes.apply(file).unwrap(), "xzy\n");
+    }
+
+    #[test]
+    fn duplicate_suggestions_are_applied_once() {
+        let file = FileId::new("fix/duplicate.rs", "let x = 1;\n");
+        let diagnostics = [
+            fix(vec![part(file, 4, 5, "y")]),
+            fix(vec![part(file, 4, 5, "y")]),
+        ];
+        let fixes = Fixes::new(&diagnostics);
+
+        assert_eq!(fixes.skipped(), 0);
+        assert_eq!(fixes.apply(file).unwrap(), "let y = 1;\n");
+    }
+
+    #[test]
+    fn only_machine_applicable_suggestions_are_used() {
+        let file = FileId::new("fix/applicability.rs", "let x = 1;\n");
+        let diagnostics = [error().suggestion(
+            CompactSpan::new(file, 4, 5),
+            "y",
+            "fix",
+            Applicability::MaybeIncorrect,
+        )];
+        let fixes = Fixes::new(&diagnostics);
+
+        assert!(fixes.is_empty());
+        assert_eq!(fixes.apply(file), None);
+    }
+
+    #[test]
+    fn multipart_suggestions_are_rejected_as_a_whole() {
+        let file = FileId::new("fix/multipart.rs", "let x = 1;\nlet y = 2;\n");
+        let diagnostics = [
+            fix(vec![part(file, 19, 20, "3"), part(file, 4, 5, "b")]),
+            fix(vec![part(file, 4, 5, "a")]),
+            fix(vec![part(file, 11, 14, "var"), part(file, 12, 13, "x")]),
+        ];
+        let fixes = Fixes::new(&diagnostics);
+
+        assert_eq!(fixes.skipped(), 2);
+        assert_eq!(fixes.edits(file).len(), 1);
+        assert_eq!(fixes.apply(file).unwrap(), "let a = 1;\nlet y = 2;\n");
+    }
+
+    #[test]
+    fn multipart_suggestions_may_span_files() {
+        let a = FileId::new("fix/multifile_a.rs", "use b::f;\n");
+        let b = FileId::new("fix/multifile_b.rs", "fn f() {}\n");
+        let diagnostics = [fix(vec![part(a, 7, 8, "g"), part(b, 3, 4, "g")])];
+        let fixes = Fixes::new(&diagnostics);
+
+        assert_eq!(fixes.files().collect::<Vec<_>>(), vec![a, b]);
+        assert_eq!(fixes.apply(a).unwrap(), "use b::g;\n");
+        assert_eq!(fixes.apply(b).unwrap(), "fn g() {}\n");
+    }
+
+    #[test]
+    fn nearby_changes_share_a_hunk() {
+        let (file, lines) = numbered("fix/merged.rs");
+        let diagnostics = [
+            fix(vec![part(file, lines[1], lines[1] + 1, "two")]),
+            fix(vec![part(file, lines[5], lines[5] + 1, "six")]),
+        ];
+
+        assert_eq!(
+            diff(&Fixes::new(&diagnostics)),
+            "--- a/fix/merged.rs\n\
+             +++ b/fix/merged.rs\n\
+             @@ -1,9 +1,9 @@\n \
+             1\n\
+             -2\n\
+             +two\n \
+             3\n \
+             4\n \
+             5\n\
+             -6\n\
+             +six\n \
+             7\n \
+             8\n \
+             9\n"
+        );
+    }
+
+    #[test]
+    fn distant_changes_get_their_own_hunks() {
+        let (file, lines) = numbered("fix/separate.rs");
+        let diagnostics = [
+            fix(vec![part(file, lines[1], lines[2], "two\nzwei\n")]),
+            fix(vec![part(file, lines[15], lines[15] + 2, "sixteen")]),
+        ];
+
+        assert_eq!(
+            diff(&Fixes::new(&diagnostics)),
+            "--- a/fix/separate.rs\n\
+             +++ b/fix/separate.rs\n\
+             @@ -1,5 +1,6 @@\n \
+             1\n\
+             -2\n\
+             +two\n\
+             +zwei\n \
+             3\n \
+             4\n \
+             5\n\
+             @@ -13,7 +14,7 @@\n \
+             13\n \
+             14\n \
+             15\n\
+             -16\n\
+             +sixteen\n \
+             17\n \
+             18\n \
+             19\n"
+        );
+    }
+
+    #[test]
+    fn missing_newline_at_end_of_file_is_marked() {
+        let file = FileId::new("fix/no_newline.rs", "a\nb");
+        let diagnostics = [fix(vec![part(file, 2, 3, "c")])];
+
+        assert_eq!(
+            diff(&Fixes::new(&diagnostics)),
+            "--- a/fix/no_newline.rs\n\
+             +++ b/fix/no_newline.rs\n\
+             @@ -1,2 +1,2 @@\n \
+             a\n\
+             -b\n\
+             \\ No newline at end of file\n\
+             +c\n\
+             \\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn insertions_at_end_of_file() {
+        let file = FileId::new("fix/eof.rs", "a\n");
+        let diagnostics = [fix(vec![part(file, 2, 2, "b\n")])];
+        let fixes = Fixes::new(&diagnostics);
+
+        assert_eq!(fixes.apply(file).unwrap(), "a\nb\n");
+        assert_eq!(
+            diff(&fixes),
+            "--- a/fix/eof.rs\n\
+             +++ b/fix/eof.rs\n\
+             @@ -1 +1,2 @@\n\
+             -a\n\
+             +a\n\
+             +b\n"
+        );
+    }
+
+    #[test]
+    fn write_files_rewrites_the_source() {
+        let path = std::env::temp_dir().join(format!("diagnostics-fix-{}.rs", std::process::id()));
+
+        std::fs::write(&path, "let x = 1;\n").unwrap();
+
+        let file = FileId::new(&path, std::fs::read_to_string(&path).unwrap());
+        let diagnostics = [fix(vec![part(file, 4, 5, "y")])];
+
+        Fixes::new(&diagnostics).write_files().unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, "let y = 1;\n");
+    }
+}
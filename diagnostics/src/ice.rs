@@ -0,0 +1,95 @@
+use crate::{Diagnostic, Emitter, HumanEmitter, Reporter, Severity};
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::panic::PanicHookInfo;
+use std::sync::Arc;
+use termcolor::ColorChoice;
+
+/// What to tell users when the tool crashes, see [`install_ice_hook`].
+#[derive(Debug, Clone)]
+pub struct IceOptions {
+    pub tool_name: String,
+    pub tool_version: Option<String>,
+    /// Where users should file the bug report.
+    pub bug_report_url: Option<String>,
+    /// Whether to capture a backtrace regardless of `RUST_BACKTRACE`.
+    pub backtrace: bool,
+}
+
+impl IceOptions {
+    pub fn new(tool_name: impl Into<String>) -> Self {
+        IceOptions {
+            tool_name: tool_name.into(),
+            tool_version: None,
+            bug_report_url: None,
+            backtrace: true,
+        }
+    }
+}
+
+/// Replaces the panic hook with one that reports a panic as an internal compiler error.
+///
+/// The diagnostics already collected by `reporter` are emitted first, so they are not lost
+/// when the process dies. Panics raised by [`FatalError::raise`](crate::FatalError::raise)
+/// do not invoke the hook.
+pub fn install_ice_hook(reporter: Arc<Reporter>, options: IceOptions) {
+    std::panic::set_hook(Box::new(move |info| {
+        let diagnostic = ice(info, &options);
+
+        if let Err(diagnostic) = reporter.report_ice(diagnostic) {
+            // The panic happened while the reporter was in use.
+            let _ = HumanEmitter::stderr(ColorChoice::Auto).emit(&diagnostic);
+        }
+    }));
+}
+
+fn ice(info: &PanicHookInfo, options: &IceOptions) -> Diagnostic {
+    let payload = info.payload();
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "Box<dyn Any>"
+    };
+
+    let mut diagnostic = Diagnostic::new(
+        Severity::Bug,
+        None,
+        format!("internal compiler error: {}", message),
+    );
+
+    if let Some(location) = info.location() {
+        diagnostic = diagnostic.note(format!("panicked at {}", location));
+    }
+
+    let backtrace = if options.backtrace {
+        Backtrace::force_capture()
+    } else {
+        Backtrace::capture()
+    };
+
+    diagnostic = match backtrace.status() {
+        BacktraceStatus::Captured => diagnostic.note(format!("backtrace:\n{}", backtrace)),
+        _ => diagnostic.note("run with `RUST_BACKTRACE=1` to display a backtrace"),
+    };
+
+    diagnostic = match &options.bug_report_url {
+        Some(url) => diagnostic.note(format!(
+            "this is a bug in {}, please file a bug report at {}",
+            options.tool_name, url
+        )),
+        None => diagnostic.note(format!("this is a bug in {}", options.tool_name)),
+    };
+
+    let version = match &options.tool_version {
+        Some(version) => format!("{} {}", options.tool_name, version),
+        None => options.tool_name.clone(),
+    };
+
+    diagnostic.note(format!(
+        "{} running on {}-{}",
+        version,
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    ))
+}
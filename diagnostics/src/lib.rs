@@ -1,15 +1,30 @@
 #![feature(decl_macro)]
 
+mod code;
 mod diagnostic;
 mod emit;
 mod file;
+mod fix;
+mod ice;
+mod lint;
+pub mod lsp;
 mod reporter;
 mod span;
 
+pub use code::*;
 pub use diagnostic::*;
+pub use emit::json::{
+    emit as emit_json, JsonDiagnostic, JsonLabel, JsonSpan, JsonSubstitution, JsonSuggestion,
+};
+pub use emit::sarif::{Log as SarifLog, SarifOptions};
+pub use emit::{Emitter, HumanEmitter, JsonEmitter, RenderOptions, ShortEmitter};
 pub use file::*;
+pub use fix::{Edit, Fixes};
+pub use ice::{install_ice_hook, IceOptions};
+pub use lint::*;
 pub use reporter::*;
 pub use span::*;
+pub use termcolor;
 
 pub macro unimpl($span:expr, $msg:literal $(, $arg:expr)*) {
     $crate::Diagnostic::new($crate::Severity::Bug, None, format!("Unimplemented feature: {}", format!($msg $(, $arg)*)))
@@ -21,6 +36,12 @@ pub macro unreach($span:expr, $msg:literal $(, $arg:expr)*) {
         .label($crate::Severity::Bug, $span, None::<String>)
 }
 
+/// A bug that is only reported if no error was reported, see [`Reporter::delay_bug`].
+pub macro delayed_bug($span:expr, $msg:literal $(, $arg:expr)*) {
+    $crate::Diagnostic::new($crate::Severity::Bug, None, format!($msg $(, $arg)*))
+        .label($crate::Severity::Bug, $span, None::<String>)
+}
+
 pub macro report_error {
     ($reporter:expr, $sev:ident $code:literal, $span:expr, ($($msg:tt)+) $(, ($lsev:ident $lspan:expr, $($lmsg:tt)+))*) => {
         $crate::report_error!(@emit
@@ -0,0 +1,172 @@
+use crate::{Diagnostic, Severity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+    /// Like `Deny`, but the level can no longer be changed afterwards.
+    Forbid,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Lint {
+    /// The name in `SCREAMING_SNAKE_CASE`, see [`Lint::name_lower`].
+    pub name: &'static str,
+    pub default_level: Level,
+    pub description: &'static str,
+    pub group: Option<&'static str>,
+}
+
+/// Declares a static [`Lint`].
+///
+/// ```ignore
+/// declare_lint!(pub UNUSED_VARIABLE, Warn, "detects variables that are never read", group: "unused");
+/// ```
+pub macro declare_lint {
+    ($vis:vis $name:ident, $level:ident, $desc:expr, group: $group:expr $(,)?) => {
+        $vis static $name: $crate::Lint = $crate::Lint {
+            name: stringify!($name),
+            default_level: $crate::Level::$level,
+            description: $desc,
+            group: Some($group),
+        };
+    },
+
+    ($vis:vis $name:ident, $level:ident, $desc:expr $(,)?) => {
+        $vis static $name: $crate::Lint = $crate::Lint {
+            name: stringify!($name),
+            default_level: $crate::Level::$level,
+            description: $desc,
+            group: None,
+        };
+    }
+}
+
+/// The configured level of every lint and lint group, in the order the levels were set.
+#[derive(Debug, Default, Clone)]
+pub struct LintLevels {
+    levels: Vec<(String, Level)>,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+            Level::Forbid => "forbid",
+        }
+    }
+
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            Level::Allow => None,
+            Level::Warn => Some(Severity::Warning),
+            Level::Deny | Level::Forbid => Some(Severity::Error),
+        }
+    }
+}
+
+impl std::str::FromStr for Level {
+    type Err = ();
+
+    fn from_str(src: &str) -> Result<Level, ()> {
+        match src.to_lowercase().as_str() {
+            "a" | "allow" => Ok(Level::Allow),
+            "w" | "warn" => Ok(Level::Warn),
+            "d" | "deny" => Ok(Level::Deny),
+            "f" | "forbid" => Ok(Level::Forbid),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Lint {
+    /// The name as written on the command line, e.g. `unused-variable`.
+    pub fn name_lower(&self) -> String {
+        normalize(self.name)
+    }
+}
+
+impl LintLevels {
+    pub fn new() -> Self {
+        LintLevels::default()
+    }
+
+    /// Sets the level of a lint or lint group, overriding any level that was set before.
+    ///
+    /// Returns `false` and leaves the level unchanged if `name` itself was forbidden. Only the
+    /// exact name is checked, so a lint in a forbidden group can still be set here, but `level`
+    /// keeps reporting it as forbidden.
+    pub fn set(&mut self, name: &str, level: Level) -> bool {
+        let name = normalize(name);
+
+        if self
+            .levels
+            .iter()
+            .any(|(n, l)| *n == name && *l == Level::Forbid)
+        {
+            return level == Level::Forbid;
+        }
+
+        self.levels.push((name, level));
+        true
+    }
+
+    pub fn level(&self, lint: &Lint) -> Level {
+        self.lookup(lint).0
+    }
+
+    /// Returns the level of `lint` along with the lint or group name it was set by, or `None` if
+    /// it has its default level.
+    fn lookup(&self, lint: &Lint) -> (Level, Option<&str>) {
+        if lint.default_level == Level::Forbid {
+            return (Level::Forbid, None);
+        }
+
+        let name = lint.name_lower();
+        let group = lint.group.map(normalize);
+        let matches = |n: &String| *n == name || Some(n) == group.as_ref();
+
+        self.levels
+            .iter()
+            .find(|(n, l)| matches(n) && *l == Level::Forbid)
+            .or_else(|| self.levels.iter().rev().find(|(n, _)| matches(n)))
+            .map_or((lint.default_level, None), |(n, level)| {
+                (*level, Some(n.as_str()))
+            })
+    }
+
+    /// Applies the level of the diagnostic's lint to it, returning `None` if the lint is allowed.
+    pub fn apply(&self, mut diagnostic: Diagnostic) -> Option<Diagnostic> {
+        let lint = match diagnostic.lint {
+            Some(lint) => lint,
+            None => return Some(diagnostic),
+        };
+
+        let (level, source) = self.lookup(lint);
+        let name = lint.name_lower();
+
+        diagnostic.severity = level.severity()?;
+
+        let note = match source {
+            None => format!("`{}` is set to `{}` by default", name, level.as_str()),
+            Some(source) if source == name => {
+                format!("`{}` is set to `{}`", name, level.as_str())
+            }
+            Some(group) => format!(
+                "`{}` is set to `{}` by the `{}` group",
+                name,
+                level.as_str(),
+                group
+            ),
+        };
+
+        Some(diagnostic.note(note))
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase().replace('_', "-")
+}
@@ -0,0 +1,263 @@
+use crate::{FileId, FileInfo, Severity, Span};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u32,
+    /// Column in UTF-16 code units.
+    pub character: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Location {
+    pub uri: String,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DiagnosticSeverity(pub i32);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DiagnosticRelatedInformation {
+    pub location: Location,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub range: Range,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<DiagnosticSeverity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub related_information: Option<Vec<DiagnosticRelatedInformation>>,
+}
+
+impl DiagnosticSeverity {
+    pub const ERROR: DiagnosticSeverity = DiagnosticSeverity(1);
+    pub const WARNING: DiagnosticSeverity = DiagnosticSeverity(2);
+    pub const INFORMATION: DiagnosticSeverity = DiagnosticSeverity(3);
+    pub const HINT: DiagnosticSeverity = DiagnosticSeverity(4);
+}
+
+impl From<Severity> for DiagnosticSeverity {
+    fn from(severity: Severity) -> DiagnosticSeverity {
+        match severity {
+            Severity::Bug | Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Info => DiagnosticSeverity::INFORMATION,
+            Severity::Help => DiagnosticSeverity::HINT,
+        }
+    }
+}
+
+/// Converts a diagnostic into an LSP diagnostic for the file of its primary span.
+///
+/// Every other spanned label, primary or secondary, becomes related information.
+/// Spanless labels and children are appended to the message, since LSP has no notion of
+/// notes. Children with a span become related information.
+pub fn convert(diag: &crate::Diagnostic) -> Option<(FileId, Diagnostic)> {
+    let primary = diag.primary_span()?;
+    let mut message = diag.message.clone();
+    let mut related = Vec::new();
+    let mut seen_primary = false;
+
+    for label in &diag.labels {
+        match label.span {
+            Some(span) if span == primary && !seen_primary => seen_primary = true,
+            Some(span) => related.push(DiagnosticRelatedInformation {
+                location: location(span),
+                message: label
+                    .message
+                    .clone()
+                    .unwrap_or_else(|| diag.message.clone()),
+            }),
+            None => {
+                if let Some(msg) = &label.message {
+                    message.push_str(&format!("\n{}: {}", label.severity.to_string(), msg));
+                }
+            }
+        }
+    }
+
+    for child in &diag.children {
+        match child.primary_span() {
+            Some(span) => related.push(DiagnosticRelatedInformation {
+                location: location(span),
+                message: child.message.clone(),
+            }),
+            None => message.push_str(&format!(
+                "\n{}: {}",
+                child.severity.to_string(),
+                child.message
+            )),
+        }
+    }
+
+    Some((
+        primary.file,
+        Diagnostic {
+            range: range(primary),
+            severity: Some(diag.severity.into()),
+            code: diag.code.map(|code| format!("{:0>4}", code)),
+            source: None,
+            message,
+            related_information: if related.is_empty() {
+                None
+            } else {
+                Some(related)
+            },
+        },
+    ))
+}
+
+/// Returns the `file://` URI of a file, resolving relative names against the working directory.
+pub fn uri(file: FileId) -> String {
+    let mut path = file.info().name.clone();
+
+    if path.is_relative() {
+        if let Ok(cwd) = std::env::current_dir() {
+            path = cwd.join(path);
+        }
+    }
+
+    format!("file://{}", crate::file::path_to_uri(&path))
+}
+
+pub fn location(span: Span) -> Location {
+    Location {
+        uri: uri(span.file),
+        range: range(span),
+    }
+}
+
+pub fn range(span: Span) -> Range {
+    let file = span.file.info();
+
+    Range {
+        start: position(&file, span.start),
+        end: position(&file, span.end),
+    }
+}
+
+/// Maps a byte-based position to a UTF-16 based LSP position.
+pub fn position(file: &FileInfo, pos: crate::Position) -> Position {
+    let text = file.line(pos.line).unwrap_or("");
+    let mut col = pos.col.min(text.len());
+
+    while !text.is_char_boundary(col) {
+        col -= 1;
+    }
+
+    Position {
+        line: pos.line as u32,
+        character: text[..col].encode_utf16().count() as u32,
+    }
+}
+
+/// Maps an LSP position back to a byte-based position, clamping it to the file's contents.
+pub fn from_position(file: &FileInfo, pos: Position) -> crate::Position {
+    let line = pos.line as usize;
+    let (start, text) = match (file.line_start(line), file.line(line)) {
+        (Some(start), Some(text)) => (start, text),
+        _ => return file.position(file.source.len()),
+    };
+
+    let mut units = 0;
+    let mut col = text.len();
+
+    for (idx, ch) in text.char_indices() {
+        if units >= pos.character as usize {
+            col = idx;
+            break;
+        }
+
+        units += ch.len_utf16();
+    }
+
+    crate::Position {
+        offset: start + col,
+        line,
+        col,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lsp(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn astral_characters_take_two_code_units() {
+        let file = FileInfo::new("lsp/astral.rs", "a\u{1F600}b\n");
+
+        assert_eq!(position(&file, file.position(0)), lsp(0, 0));
+        assert_eq!(position(&file, file.position(1)), lsp(0, 1));
+        assert_eq!(position(&file, file.position(5)), lsp(0, 3));
+        assert_eq!(position(&file, file.position(6)), lsp(0, 4));
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        let file = FileInfo::new(
+            "lsp/round_trip.rs",
+            "x\n\u{1F600}\u{10348} = \"é\";\r\n\u{1F600}",
+        );
+
+        for (offset, c) in file.source.char_indices() {
+            let pos = file.position(offset);
+
+            // The `\n` of a `\r\n` maps to the end of the line, like the `\r` before it.
+            if c == '\n' && file.source[..offset].ends_with('\r') {
+                assert_eq!(
+                    from_position(&file, position(&file, pos)),
+                    file.position(offset - 1)
+                );
+            } else {
+                assert_eq!(from_position(&file, position(&file, pos)), pos);
+            }
+        }
+
+        let end = file.position(file.source.len());
+
+        assert_eq!(position(&file, end), lsp(2, 2));
+        assert_eq!(from_position(&file, position(&file, end)), end);
+    }
+
+    #[test]
+    fn positions_inside_a_character_are_rounded() {
+        let file = FileInfo::new("lsp/inside.rs", "\u{1F600}b\n");
+
+        // Byte columns inside a character round down to its start.
+        assert_eq!(position(&file, file.position(2)), lsp(0, 0));
+        // Code units inside a surrogate pair round up to the end of the character.
+        assert_eq!(from_position(&file, lsp(0, 1)), file.position(4));
+    }
+
+    #[test]
+    fn positions_past_the_end_are_clamped() {
+        let file = FileInfo::new("lsp/clamp.rs", "ab\n\u{1F600}\n");
+
+        assert_eq!(from_position(&file, lsp(0, 10)), file.position(2));
+        assert_eq!(from_position(&file, lsp(1, 10)), file.position(7));
+        assert_eq!(
+            from_position(&file, lsp(9, 0)),
+            file.position(file.source.len())
+        );
+    }
+}
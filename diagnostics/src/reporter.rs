@@ -1,46 +1,521 @@
-use crate::{Diagnostic, Severity, Span};
-use std::sync::Mutex;
+use crate::{
+    Diagnostic, Emitter, FileId, Fixes, HumanEmitter, LintLevels, SarifOptions, Severity, Span,
+};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use termcolor::ColorChoice;
 
-#[derive(Default)]
 pub struct Reporter {
-    diagnostics: Mutex<Vec<Diagnostic>>,
+    state: Mutex<State>,
+    emitter: Mutex<Box<dyn Emitter + Send>>,
+    lint_levels: LintLevels,
+    exit_code: Option<i32>,
+    error_limit: Option<usize>,
+}
+
+#[derive(Default)]
+struct State {
+    diagnostics: Vec<Diagnostic>,
+    /// The number of diagnostics at the front of `diagnostics` that were already emitted.
+    reported: usize,
+    /// The handles of the diagnostics that were not emitted yet, in the same order.
+    pending_ids: Vec<DiagnosticId>,
+    next_id: usize,
+    errors: usize,
+    /// Bugs that are only emitted if no error is reported, see `Reporter::delay_bug`.
+    delayed_bugs: Vec<(DiagnosticId, Diagnostic)>,
+    limit_reached: bool,
+    limit_reported: bool,
+}
+
+/// A handle to a diagnostic that was added to a `Reporter`, which can be used to change
+/// the diagnostic until it is emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DiagnosticId(usize);
+
+/// Returned by `Reporter::fatal` after a fatal diagnostic was emitted.
+///
+/// Callers should stop what they are doing, either by returning it as an error or by
+/// calling [`FatalError::raise`] and catching it higher up with [`catch_fatal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub struct FatalError;
+
+/// The diagnostics emitted by a `Reporter` so far.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    pub errors: usize,
+    pub warnings: usize,
+    pub bugs: usize,
+}
+
+impl Default for Reporter {
+    fn default() -> Self {
+        Reporter::new(HumanEmitter::stderr(ColorChoice::Auto))
+    }
 }
 
 impl Reporter {
-    pub fn add(&self, diagnostic: Diagnostic) {
-        let is_bug = diagnostic.severity == Severity::Bug;
+    pub fn new(emitter: impl Emitter + Send + 'static) -> Self {
+        Reporter {
+            state: Mutex::new(State::default()),
+            emitter: Mutex::new(Box::new(emitter)),
+            lint_levels: LintLevels::default(),
+            exit_code: None,
+            error_limit: None,
+        }
+    }
+
+    pub fn with_lint_levels(mut self, levels: LintLevels) -> Self {
+        self.lint_levels = levels;
+        self
+    }
+
+    /// Makes `report` exit the process with `code` when compilation should abort.
+    ///
+    /// # Panics
+    /// Panics if `code` is zero, since that would report success to the shell.
+    pub fn exit_on_error(mut self, code: i32) -> Self {
+        assert_ne!(code, 0, "exit code for errors must be nonzero");
+        self.exit_code = Some(code);
+        self
+    }
+
+    /// Stops collecting diagnostics once `limit` errors were added.
+    pub fn error_limit(mut self, limit: usize) -> Self {
+        self.error_limit = Some(limit);
+        self
+    }
+
+    /// Adds a diagnostic to be emitted by the next `report`.
+    ///
+    /// The returned handle can be used to cancel or change the diagnostic until then.
+    /// Diagnostics that are dropped right away, such as allowed lints or exact duplicates of
+    /// diagnostics collected earlier, get a handle that does not refer to anything. Duplicates
+    /// also do not count towards the error limit.
+    ///
+    /// Bugs are fatal: they are emitted immediately, after which the current thread unwinds
+    /// with a [`FatalError`].
+    pub fn add(&self, diagnostic: Diagnostic) -> DiagnosticId {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id();
+        let diagnostic = match self.lint_levels.apply(diagnostic) {
+            Some(diagnostic) => diagnostic,
+            None => return id,
+        };
+
+        if diagnostic.severity == Severity::Bug {
+            drop(state);
+            self.fatal(diagnostic).raise();
+        }
+
+        if state.limit_reached || state.diagnostics.contains(&diagnostic) {
+            return id;
+        }
 
-        self.diagnostics.lock().unwrap().push(diagnostic);
+        if diagnostic.severity == Severity::Error {
+            if self.error_limit.is_some_and(|limit| state.errors >= limit) {
+                state.limit_reached = true;
+                return id;
+            }
 
-        if is_bug {
-            self.report(true);
+            state.errors += 1;
         }
+
+        state.diagnostics.push(diagnostic);
+        state.pending_ids.push(id);
+        id
+    }
+
+    /// Emits everything collected so far together with `diagnostic` right away.
+    pub fn fatal(&self, diagnostic: Diagnostic) -> FatalError {
+        let mut state = self.state.lock().unwrap();
+
+        if diagnostic.severity == Severity::Error {
+            state.errors += 1;
+        }
+
+        let id = state.next_id();
+
+        state.diagnostics.push(diagnostic);
+        state.pending_ids.push(id);
+        drop(state);
+        self.report();
+
+        FatalError
     }
 
+    /// Records a bug for a state that is only legal after an earlier error.
+    ///
+    /// The next `report` emits it as a `Severity::Bug` if no error was reported by then,
+    /// and drops it otherwise.
+    pub fn delay_bug(&self, mut diagnostic: Diagnostic) -> DiagnosticId {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id();
+
+        diagnostic.severity = Severity::Bug;
+        state.delayed_bugs.push((id, diagnostic));
+        id
+    }
+
+    /// Drops a diagnostic that was not emitted yet.
+    ///
+    /// Returns `false` if the diagnostic was already emitted or dropped.
+    pub fn cancel(&self, id: DiagnosticId) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(i) = state.pending_ids.iter().position(|&p| p == id) {
+            let index = state.reported + i;
+            let diagnostic = state.diagnostics.remove(index);
+
+            state.pending_ids.remove(i);
+
+            if diagnostic.severity == Severity::Error {
+                state.errors -= 1;
+            }
+
+            return true;
+        }
+
+        let delayed = state.delayed_bugs.len();
+
+        state.delayed_bugs.retain(|(p, _)| *p != id);
+        state.delayed_bugs.len() != delayed
+    }
+
+    /// Changes the severity of a diagnostic that was not emitted yet, e.g. from an error
+    /// to a warning once a recovery path turned out to be valid.
+    ///
+    /// Returns `false` if the diagnostic was already emitted or dropped.
+    pub fn downgrade(&self, id: DiagnosticId, severity: Severity) -> bool {
+        self.modify(id, |mut diagnostic| {
+            diagnostic.severity = severity;
+            diagnostic
+        })
+    }
+
+    /// Changes a diagnostic that was not emitted yet with the usual builder methods:
+    ///
+    /// ```ignore
+    /// reporter.modify(id, |d| d.note("the previous item was not closed"));
+    /// ```
+    ///
+    /// Returns `false` if the diagnostic was already emitted or dropped.
+    pub fn modify(&self, id: DiagnosticId, f: impl FnOnce(Diagnostic) -> Diagnostic) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let State {
+            diagnostics,
+            reported,
+            pending_ids,
+            errors,
+            delayed_bugs,
+            ..
+        } = &mut *state;
+
+        let (diagnostic, counted) = match pending_ids.iter().position(|&p| p == id) {
+            Some(i) => (&mut diagnostics[*reported + i], true),
+            None => match delayed_bugs.iter_mut().find(|(p, _)| *p == id) {
+                Some((_, diagnostic)) => (diagnostic, false),
+                None => return false,
+            },
+        };
+
+        let old = std::mem::replace(diagnostic, Diagnostic::new(Severity::Bug, None, ""));
+        let was_error = old.severity == Severity::Error;
+
+        *diagnostic = f(old);
+
+        if counted {
+            match (was_error, diagnostic.severity == Severity::Error) {
+                (true, false) => *errors -= 1,
+                (false, true) => *errors += 1,
+                _ => {}
+            }
+        }
+
+        true
+    }
+
+    /// Whether the error limit was hit, after which no more diagnostics are collected.
+    pub fn limit_reached(&self) -> bool {
+        self.state.lock().unwrap().limit_reached
+    }
+
+    /// Drops every diagnostic that was not emitted yet with `code` and `span` as its
+    /// primary span. Prefer [`Reporter::cancel`], which cannot hit unrelated diagnostics.
     pub fn remove(&self, span: Span, code: u16) {
-        self.diagnostics
-            .lock()
-            .unwrap()
-            .retain(|diag| !(diag.labels[0].span == Some(span) && diag.code == Some(code)));
+        let mut state = self.state.lock().unwrap();
+        let reported = state.reported;
+        let pending = state.diagnostics.split_off(reported);
+        let ids = std::mem::take(&mut state.pending_ids);
+
+        for (diag, id) in pending.into_iter().zip(ids) {
+            if diag.primary_span() == Some(span) && diag.code == Some(code) {
+                if diag.severity == Severity::Error {
+                    state.errors -= 1;
+                }
+            } else {
+                state.diagnostics.push(diag);
+                state.pending_ids.push(id);
+            }
+        }
     }
 
     pub fn has_errors(&self) -> bool {
-        self.diagnostics
+        self.state
             .lock()
             .unwrap()
+            .diagnostics
             .iter()
             .any(|d| d.severity == Severity::Error || d.severity == Severity::Bug)
     }
 
-    pub fn report(&self, exit: bool) {
-        self.diagnostics.lock().unwrap().sort_by_key(|d| d.severity);
+    /// Collects the machine-applicable suggestions of every diagnostic added so far.
+    pub fn fixes(&self) -> Fixes {
+        Fixes::new(self.state.lock().unwrap().diagnostics.iter())
+    }
+
+    pub fn write_sarif(
+        &self,
+        options: &SarifOptions,
+        writer: impl std::io::Write,
+    ) -> std::io::Result<()> {
+        crate::emit::sarif::write(&self.state.lock().unwrap().diagnostics, options, writer)
+    }
 
-        for d in self.diagnostics.lock().unwrap().iter() {
-            let _ = crate::emit::emit(d);
+    /// Emits every diagnostic that was added since the last report in source order.
+    /// Exact duplicates of earlier diagnostics are dropped.
+    ///
+    /// Delayed bugs are emitted along with them if there are no errors, and dropped otherwise.
+    ///
+    /// If an exit code was set with `exit_on_error` and compilation should abort,
+    /// the process exits after writing the summary of [`Outcome::summary`] instead of returning.
+    /// Otherwise no summary is written, so this can be called after every phase.
+    pub fn report(&self) -> Outcome {
+        let state = self.state.lock().unwrap();
+        let emitter = self.emitter.lock().unwrap();
+
+        self.report_locked(state, emitter, true, false)
+    }
+
+    /// Like `report`, but always ends with the summary of all diagnostics so far.
+    ///
+    /// Drivers call this once when compilation is done.
+    pub fn finish(&self) -> Outcome {
+        let state = self.state.lock().unwrap();
+        let emitter = self.emitter.lock().unwrap();
+
+        self.report_locked(state, emitter, true, true)
+    }
+
+    /// Reports everything collected so far followed by an internal compiler error, for use
+    /// from a panic hook.
+    ///
+    /// This neither blocks nor exits, since the panic may have happened while the reporter
+    /// was in use and may still be caught. Returns the diagnostic if the reporter is busy.
+    pub(crate) fn report_ice(&self, diagnostic: Diagnostic) -> Result<Outcome, Diagnostic> {
+        let (mut state, emitter) = match (try_lock(&self.state), try_lock(&self.emitter)) {
+            (Some(state), Some(emitter)) => (state, emitter),
+            _ => return Err(diagnostic),
+        };
+
+        state.diagnostics.push(diagnostic);
+
+        let id = state.next_id();
+
+        state.pending_ids.push(id);
+
+        Ok(self.report_locked(state, emitter, false, false))
+    }
+
+    fn report_locked(
+        &self,
+        mut state: MutexGuard<State>,
+        mut emitter: MutexGuard<Box<dyn Emitter + Send>>,
+        exit: bool,
+        finish: bool,
+    ) -> Outcome {
+        let State {
+            diagnostics,
+            reported,
+            pending_ids,
+            errors,
+            delayed_bugs,
+            ..
+        } = &mut *state;
+
+        let mut pending = diagnostics.split_off(*reported);
+
+        pending_ids.clear();
+
+        if *errors == 0 {
+            pending.extend(delayed_bugs.drain(..).map(|(_, d)| d));
+        } else {
+            delayed_bugs.clear();
+        }
+
+        pending.sort_by_cached_key(sort_key);
+
+        let unique = {
+            let mut seen = diagnostics.iter().collect::<HashSet<_>>();
+
+            pending.iter().map(|d| seen.insert(d)).collect::<Vec<_>>()
+        };
+
+        diagnostics.extend(pending.into_iter().zip(unique).filter_map(|(d, unique)| {
+            if unique {
+                Some(d)
+            } else {
+                None
+            }
+        }));
+
+        for d in &diagnostics[*reported..] {
+            let _ = emitter.emit(d);
+        }
+
+        *reported = diagnostics.len();
+
+        let outcome = Outcome::new(diagnostics);
+
+        if state.limit_reached && !state.limit_reported {
+            let _ = emitter.emit(&Diagnostic::new(
+                Severity::Error,
+                None,
+                "too many errors, stopping",
+            ));
+
+            state.limit_reported = true;
+        }
+
+        let exit_code = self.exit_code.filter(|_| exit && outcome.should_abort());
+
+        if finish || exit_code.is_some() {
+            if let Some(summary) = outcome.summary() {
+                let _ = emitter.emit(&summary);
+            }
+        }
+
+        let _ = emitter.flush();
+
+        match exit_code {
+            Some(code) => std::process::exit(code),
+            None => outcome,
         }
+    }
+}
+
+impl State {
+    fn next_id(&mut self) -> DiagnosticId {
+        self.next_id += 1;
+        DiagnosticId(self.next_id - 1)
+    }
+}
+
+/// Locks `mutex` unless that would block, ignoring poisoning.
+fn try_lock<T: ?Sized>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+impl FatalError {
+    /// Unwinds the current thread with this error as the panic payload.
+    ///
+    /// This does not invoke the panic hook, so nothing besides the already emitted
+    /// diagnostics is printed.
+    pub fn raise(self) -> ! {
+        std::panic::resume_unwind(Box::new(self))
+    }
+}
+
+impl std::fmt::Display for FatalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("fatal error")
+    }
+}
+
+impl std::error::Error for FatalError {}
 
-        if self.has_errors() && exit {
-            std::process::exit(0);
+/// Runs `f`, turning a raised [`FatalError`] into an `Err`. Other panics keep unwinding.
+pub fn catch_fatal<R>(f: impl FnOnce() -> R) -> Result<R, FatalError> {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(value) => Ok(value),
+        Err(payload) if payload.is::<FatalError>() => Err(FatalError),
+        Err(payload) => std::panic::resume_unwind(payload),
+    }
+}
+
+type SortKey = (
+    bool,
+    Option<PathBuf>,
+    Option<FileId>,
+    usize,
+    Severity,
+    Option<u16>,
+    String,
+);
+
+/// Orders diagnostics by where they point to, placing diagnostics without a span last.
+///
+/// Files are compared by name rather than by `FileId`, since ids depend on the order in which
+/// files were added to the `SourceMap`. Looking up a name takes a lock, so the keys are meant to
+/// be computed once per diagnostic with `sort_by_cached_key`.
+fn sort_key(d: &Diagnostic) -> SortKey {
+    let span = d.primary_span();
+
+    (
+        span.is_none(),
+        span.map(|s| s.file.info().name.clone()),
+        span.map(|s| s.file),
+        span.map_or(0, |s| s.start.offset),
+        d.severity,
+        d.code,
+        d.message.clone(),
+    )
+}
+
+impl Outcome {
+    fn new(diagnostics: &[Diagnostic]) -> Outcome {
+        let mut outcome = Outcome::default();
+
+        for d in diagnostics {
+            match d.severity {
+                Severity::Bug => outcome.bugs += 1,
+                Severity::Error => outcome.errors += 1,
+                Severity::Warning => outcome.warnings += 1,
+                Severity::Info | Severity::Help => {}
+            }
         }
+
+        outcome
+    }
+
+    pub fn should_abort(&self) -> bool {
+        self.errors > 0 || self.bugs > 0
+    }
+
+    /// The `aborting due to ...` or `... warnings emitted` message, if there is anything to say.
+    pub fn summary(&self) -> Option<Diagnostic> {
+        let errors = self.errors + self.bugs;
+        let warnings = match self.warnings {
+            0 => None,
+            1 => Some(String::from("1 warning emitted")),
+            n => Some(format!("{} warnings emitted", n)),
+        };
+
+        let message = match (errors, warnings) {
+            (0, None) => return None,
+            (0, Some(warnings)) => return Some(Diagnostic::new(Severity::Warning, None, warnings)),
+            (1, None) => String::from("aborting due to 1 previous error"),
+            (n, None) => format!("aborting due to {} previous errors", n),
+            (1, Some(warnings)) => format!("aborting due to 1 previous error; {}", warnings),
+            (n, Some(warnings)) => format!("aborting due to {} previous errors; {}", n, warnings),
+        };
+
+        Some(Diagnostic::new(Severity::Error, None, message))
     }
 }
@@ -1,4 +1,6 @@
-use crate::file::FileId;
+use crate::file::{FileId, SourceMap};
+use std::convert::TryFrom;
+use std::sync::Arc;
 
 #[derive(
     Default,
@@ -17,6 +19,8 @@ pub struct Span {
     pub file: FileId,
     pub start: Position,
     pub end: Position,
+    /// The macro expansion that produced this span, or `ExpnId::ROOT` for written source.
+    pub expn: ExpnId,
 }
 
 #[derive(
@@ -38,6 +42,70 @@ pub struct Position {
     pub col: usize,
 }
 
+/// A 24-byte span that only stores byte offsets.
+///
+/// Lines and columns are looked up in the `SourceMap` when converting to a `Span`.
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct CompactSpan {
+    pub file: FileId,
+    pub lo: u32,
+    pub hi: u32,
+    pub expn: ExpnId,
+}
+
+/// Identifies a macro expansion in the global `SourceMap`.
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct ExpnId {
+    /// One past the index of the expansion, so that the default id is `ExpnId::ROOT`.
+    index: u32,
+    /// Identifies the `SourceMap` that recorded the expansion, and how often it had been cleared.
+    generation: u32,
+}
+
+/// Where the code of a macro expansion came from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExpnData {
+    pub kind: ExpnKind,
+    /// The span of the macro invocation, which may itself be part of an expansion.
+    pub call_site: Span,
+    /// The span of the macro definition, if it is known.
+    pub def_site: Option<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ExpnKind {
+    /// A function-like macro, e.g. `vec!`.
+    Bang(String),
+    /// An attribute macro, e.g. `#[test]`.
+    Attr(String),
+    /// A derive macro, e.g. `#[derive(Debug)]`.
+    Derive(String),
+}
+
 impl Span {
     pub fn empty(file: FileId) -> Span {
         Span {
@@ -58,6 +126,37 @@ impl Span {
         }
     }
 
+    pub fn with_expn(self, expn: ExpnId) -> Span {
+        Span { expn, ..self }
+    }
+
+    /// The expansions this span went through, from the innermost to the outermost one.
+    pub fn macro_backtrace(self) -> Vec<Arc<ExpnData>> {
+        let mut backtrace = Vec::<Arc<ExpnData>>::new();
+        let mut expn = self.expn;
+
+        while let Some(data) = expn.data() {
+            // A call site can only refer to an expansion that is already on the stack if
+            // the expansion table is corrupt, but that should not hang the renderer.
+            if backtrace.iter().any(|d| Arc::ptr_eq(d, &data)) {
+                break;
+            }
+
+            expn = data.call_site.expn;
+            backtrace.push(data);
+        }
+
+        backtrace
+    }
+
+    /// The outermost macro invocation this span was expanded from, or the span itself if it
+    /// is not part of an expansion.
+    pub fn source_callsite(self) -> Span {
+        self.macro_backtrace()
+            .last()
+            .map_or(self, |data| data.call_site)
+    }
+
     pub fn line_start(&self, end: bool) -> Position {
         if end {
             Position {
@@ -75,34 +174,121 @@ impl Span {
     }
 
     pub fn line_end(&self, start: bool) -> Position {
-        if start {
-            let len = self
-                .file
-                .source
-                .lines()
-                .nth(self.start.line)
-                .unwrap_or("")
-                .len();
+        let pos = if start { self.start } else { self.end };
+        let len = self.file.info().line(pos.line).unwrap_or("").len();
 
-            Position {
-                offset: self.start.offset + (len - self.start.col),
-                line: self.start.line,
-                col: len,
-            }
+        Position {
+            offset: pos.offset + (len - pos.col),
+            line: pos.line,
+            col: len,
+        }
+    }
+}
+
+impl CompactSpan {
+    /// # Panics
+    /// Panics if either offset does not fit in a `u32`.
+    pub fn new(file: FileId, lo: usize, hi: usize) -> CompactSpan {
+        CompactSpan {
+            file,
+            lo: u32::try_from(lo).expect("span offset does not fit in 32 bits"),
+            hi: u32::try_from(hi).expect("span offset does not fit in 32 bits"),
+            expn: ExpnId::ROOT,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        (self.hi - self.lo) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo == self.hi
+    }
+
+    pub fn to(self, other: CompactSpan) -> CompactSpan {
+        assert_eq!(self.file, other.file);
+        CompactSpan {
+            lo: self.lo,
+            ..other
+        }
+    }
+
+    pub fn resolve(self) -> Span {
+        let file = self.file.info();
+
+        Span {
+            file: self.file,
+            start: file.position(self.lo as usize),
+            end: file.position(self.hi as usize),
+            expn: self.expn,
+        }
+    }
+}
+
+impl From<Span> for CompactSpan {
+    fn from(span: Span) -> CompactSpan {
+        CompactSpan {
+            expn: span.expn,
+            ..CompactSpan::new(span.file, span.start.offset, span.end.offset)
+        }
+    }
+}
+
+impl From<CompactSpan> for Span {
+    fn from(span: CompactSpan) -> Span {
+        span.resolve()
+    }
+}
+
+impl From<CompactSpan> for Option<Span> {
+    fn from(span: CompactSpan) -> Option<Span> {
+        Some(span.resolve())
+    }
+}
+
+impl ExpnId {
+    /// The context of source code that was not produced by a macro.
+    pub const ROOT: ExpnId = ExpnId {
+        index: 0,
+        generation: 0,
+    };
+
+    /// Records a new expansion in the global `SourceMap`.
+    pub fn new(data: ExpnData) -> ExpnId {
+        let mut expansions = SourceMap::global().expansions.write().unwrap();
+
+        expansions.data.push(Arc::new(data));
+
+        ExpnId {
+            index: expansions.data.len() as u32,
+            generation: expansions.generation,
+        }
+    }
+
+    pub fn is_root(self) -> bool {
+        self.index == 0
+    }
+
+    /// Looks up this expansion, returning `None` for `ExpnId::ROOT` and for ids that were
+    /// handed out before the global `SourceMap` was last cleared.
+    pub fn data(self) -> Option<Arc<ExpnData>> {
+        let index = (self.index as usize).checked_sub(1)?;
+        let expansions = SourceMap::global().expansions.read().unwrap();
+
+        if self.generation == expansions.generation {
+            expansions.data.get(index).cloned()
         } else {
-            let len = self
-                .file
-                .source
-                .lines()
-                .nth(self.end.line)
-                .unwrap_or("")
-                .len();
+            None
+        }
+    }
+}
 
-            Position {
-                offset: self.end.offset + (len - self.end.col),
-                line: self.end.line,
-                col: len,
-            }
+impl ExpnKind {
+    pub fn descr(&self) -> String {
+        match self {
+            ExpnKind::Bang(name) => format!("macro `{}!`", name),
+            ExpnKind::Attr(name) => format!("attribute macro `#[{}]`", name),
+            ExpnKind::Derive(name) => format!("derive macro `{}`", name),
         }
     }
 }